@@ -0,0 +1,190 @@
+use crate::board::{Action, Board, Move, State};
+use crate::dice::Rng;
+use crate::evaluator::{max_eq, Evaluator, OpenEvaluator};
+
+// Something that chooses actions for one side.
+pub trait Agent {
+    // ToDouble, only asked when doubling is allowed
+    fn double(&mut self, board: &Board) -> bool;
+    // Doubled, the agent is the taker
+    fn take(&mut self, board: &Board) -> bool;
+    // Doubled, when a beaver or raccoon is allowed; a beaver is always taken
+    fn beaver(&mut self, _board: &Board) -> bool {
+        false
+    }
+    // ToMove, only asked when there is a choice
+    fn play(&mut self, board: &Board, moves: &[Move]) -> Move;
+    // ToChoose, the double taken after an acey-deucey
+    fn choose(&mut self, _board: &Board) -> usize {
+        6
+    }
+
+    fn act(&mut self, board: &Board) -> Action {
+        match board.state() {
+            State::ToDouble if board.can_double() && self.double(board) => Action::Double,
+            State::ToDouble => Action::NoDouble,
+            State::Doubled if board.can_beaver() && self.beaver(board) => {
+                if board.cube.beavers == 0 {
+                    Action::Beaver
+                } else {
+                    Action::Raccoon
+                }
+            }
+            State::Doubled if board.cube.beavers > 0 || self.take(board) => Action::Take,
+            State::Doubled => Action::Pass,
+            State::ToMove => {
+                let moves = board.moves();
+                if moves.len() == 1 {
+                    Action::Move(moves[0].clone())
+                } else {
+                    Action::Move(self.play(board, &moves))
+                }
+            }
+            State::ToChoose => Action::Choose(self.choose(board)),
+            s => panic!("no decision in {:?}", s),
+        }
+    }
+}
+
+pub struct RandomAgent {
+    rng: Rng,
+}
+impl RandomAgent {
+    pub fn new(rng: Rng) -> RandomAgent {
+        RandomAgent { rng }
+    }
+}
+impl Agent for RandomAgent {
+    fn double(&mut self, _: &Board) -> bool {
+        self.rng.below(2) == 0
+    }
+    fn take(&mut self, _: &Board) -> bool {
+        self.rng.below(2) == 0
+    }
+    fn play(&mut self, _: &Board, moves: &[Move]) -> Move {
+        moves[self.rng.below(moves.len())].clone()
+    }
+    fn choose(&mut self, _: &Board) -> usize {
+        self.rng.below(6) + 1
+    }
+}
+
+// never doubles, always takes and plays the first legal move
+pub struct FirstLegalAgent;
+impl Agent for FirstLegalAgent {
+    fn double(&mut self, _: &Board) -> bool {
+        false
+    }
+    fn take(&mut self, _: &Board) -> bool {
+        true
+    }
+    fn play(&mut self, _: &Board, moves: &[Move]) -> Move {
+        moves[0].clone()
+    }
+}
+
+// chooses the action with the best equity for its side
+pub struct EvaluatorAgent<E: Evaluator = OpenEvaluator> {
+    pub evaluator: E,
+}
+impl<E: Evaluator> EvaluatorAgent<E> {
+    pub fn new(evaluator: E) -> EvaluatorAgent<E> {
+        EvaluatorAgent { evaluator }
+    }
+    // the same choice as the evaluators make for the player
+    fn best(&mut self, board: &Board) -> Action {
+        let eq = self.evaluator.eval(board);
+        max_eq(&eq.actions, board.player.unwrap()).0
+    }
+}
+impl<E: Evaluator> Agent for EvaluatorAgent<E> {
+    fn double(&mut self, board: &Board) -> bool {
+        self.best(board) == Action::Double
+    }
+    fn take(&mut self, board: &Board) -> bool {
+        self.best(board) == Action::Take
+    }
+    fn beaver(&mut self, board: &Board) -> bool {
+        matches!(self.best(board), Action::Beaver | Action::Raccoon)
+    }
+    fn play(&mut self, board: &Board, moves: &[Move]) -> Move {
+        match self.best(board) {
+            Action::Move(m) => m,
+            _ => moves[0].clone(),
+        }
+    }
+    fn choose(&mut self, board: &Board) -> usize {
+        match self.best(board) {
+            Action::Choose(n) => n,
+            _ => 6,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::{Dice, Player};
+    use crate::dice::Scripted;
+    use crate::evaluator::Equities;
+    use crate::play::play_match;
+
+    #[test]
+    fn first_legal() {
+        let mut b = Board::new();
+        b.act(&Action::InitRoll(Dice(3, 1)));
+        let act = FirstLegalAgent.act(&b);
+        assert_eq!(act, b.actions()[0]);
+        b.act(&act);
+        assert_eq!(FirstLegalAgent.act(&b), Action::NoDouble);
+    }
+
+    #[test]
+    fn random() {
+        let mut a = RandomAgent::new(Rng::new(3));
+        let mut b = Board::new();
+        b.act(&Action::InitRoll(Dice(6, 5)));
+        for _ in 0..20 {
+            assert!(b.actions().contains(&a.act(&b)));
+        }
+    }
+
+    #[test]
+    fn evaluator() {
+        // White bears off the last checker with any roll
+        let b = Board::from_xg_id("XGID=-A----------------------a-:0:0:1:D:0:0:0:3:10").unwrap();
+        let mut a = EvaluatorAgent::new(OpenEvaluator::new());
+        assert_eq!(a.act(&b), Action::Pass);
+
+        let mut b =
+            Board::from_xg_id("XGID=-A----------------------a-:0:0:1:00:0:0:0:1:10").unwrap();
+        let mut white = EvaluatorAgent::new(OpenEvaluator::new());
+        let mut dice = Scripted::new(vec![Dice(2, 1)]);
+        play_match(&mut b, &mut white, &mut FirstLegalAgent, &mut dice);
+        assert_eq!(b.game.winner(), Some(Player::White));
+    }
+
+    // every action as good as any other
+    struct Even;
+    impl Evaluator for Even {
+        fn eval(&mut self, board: &Board) -> Equities {
+            Equities::new(board.actions().into_iter().map(|a| (a, 0.)).collect(), 0.)
+        }
+    }
+
+    #[test]
+    fn ties() {
+        // the agent breaks ties as the evaluators do
+        let mut b = Board::new();
+        b.act(&Action::InitRoll(Dice(3, 1)));
+        let mut a = EvaluatorAgent::new(Even);
+        for _ in 0..2 {
+            let eq = Even.eval(&b);
+            let want = max_eq(&eq.actions, b.player.unwrap()).0;
+            assert_eq!(a.best(&b), want);
+            b.act(&want);
+            b.act(&Action::NoDouble);
+            b.act(&Action::Roll(Dice(4, 2)));
+        }
+    }
+}
@@ -0,0 +1,474 @@
+// One-sided bearoff database
+// For every distribution of up to `checkers` checkers on the lowest `points` points,
+// the distribution of the number of rolls to bear them all off, and of the rolls
+// to bear off the first checker (for gammons), both playing to minimize the mean.
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::board::{Action, Board, DiceRoll, Player, State};
+use crate::evaluator::{
+    after_each, max_eq, terminal_equity, terminal_outcomes, Equities, Evaluator, Probabilities,
+};
+
+mod two_sided;
+pub use crate::cube::CubeState;
+pub use two_sided::{TwoSided, TwoSidedEvaluator};
+
+pub const MAX_ROLLS: usize = 32;
+pub type Rolls = [f32; MAX_ROLLS];
+
+const MAGIC: &[u8; 4] = b"BGB1";
+
+fn binomial(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |c, i| c * (n - i) / (i + 1))
+}
+// ways to put m checkers into `parts` ordered slots
+fn compositions(m: usize, parts: usize) -> usize {
+    if parts == 0 {
+        (m == 0) as usize
+    } else {
+        binomial(m + parts - 1, parts - 1)
+    }
+}
+// the distribution after one more roll
+fn add_next_roll(r: &mut Rolls, next: &Rolls, prob: f32) {
+    for (p, q) in r.iter_mut().skip(1).zip(next) {
+        *p += prob * q;
+    }
+}
+fn mean(r: &Rolls) -> f64 {
+    r.iter()
+        .enumerate()
+        .map(|(n, p)| n as f64 * *p as f64)
+        .sum()
+}
+
+// `pos[i]` is the number of checkers on point i + 1
+fn position_index(points: usize, checkers: usize, pos: &[usize]) -> Option<usize> {
+    if pos.len() > points && pos[points..].iter().any(|&c| c > 0) {
+        return None;
+    }
+    let total: usize = pos.iter().sum();
+    if total > checkers {
+        return None;
+    }
+    let mut left = checkers;
+    let mut index = 0;
+    for (i, &c) in pos.iter().take(points).enumerate() {
+        for v in 0..c {
+            index += compositions(left - v, points - i);
+        }
+        left -= c;
+    }
+    Some(index)
+}
+// all positions in index order
+fn all_positions(points: usize, checkers: usize) -> Vec<Vec<usize>> {
+    fn enumerate(pos: &mut Vec<usize>, point: usize, left: usize, out: &mut Vec<Vec<usize>>) {
+        if point == pos.len() {
+            out.push(pos.clone());
+            return;
+        }
+        for c in (0..=left).rev() {
+            pos[point] = c;
+            enumerate(pos, point + 1, left - c, out);
+        }
+        pos[point] = 0;
+    }
+    let mut out = vec![];
+    enumerate(&mut vec![0; points], 0, checkers, &mut out);
+    out.reverse();
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OneSided {
+    points: usize,
+    checkers: usize,
+    off: Vec<Rolls>,
+    first: Vec<Rolls>,
+}
+
+impl OneSided {
+    pub fn generate(points: usize, checkers: usize) -> OneSided {
+        assert!((1..=6).contains(&points) && checkers <= 15);
+        let size = compositions(checkers, points + 1);
+        let mut db = OneSided {
+            points,
+            checkers,
+            off: vec![[0.; MAX_ROLLS]; size],
+            first: vec![[0.; MAX_ROLLS]; size],
+        };
+        let positions = all_positions(points, checkers);
+        let mut order: Vec<usize> = (0..size).collect();
+        order.sort_by_key(|&i| pips(&positions[i]));
+        // single die moves from each position, for dice 1 to 6
+        let dies: Vec<Vec<Vec<usize>>> = positions
+            .iter()
+            .map(|pos| (1..=6).map(|d| db.play_die(pos, d)).collect())
+            .collect();
+
+        let mut off_mean = vec![0.; size];
+        let mut first_mean = vec![0.; size];
+        for i in order {
+            let pos = &positions[i];
+            let total: usize = pos.iter().sum();
+            if total == 0 {
+                db.off[i][0] = 1.;
+                db.first[i][0] = 1.;
+                continue;
+            }
+            let mut off = [0.; MAX_ROLLS];
+            let mut first = [0.; MAX_ROLLS];
+            for (dice, prob) in DiceRoll::all_with_prob() {
+                let children = after_roll(&dies, i, dice.0, dice.1);
+                let prob = prob as f32;
+                let best = *children
+                    .iter()
+                    .min_by(|&&a, &&b| off_mean[a].partial_cmp(&off_mean[b]).unwrap())
+                    .unwrap();
+                add_next_roll(&mut off, &db.off[best], prob);
+                if children
+                    .iter()
+                    .any(|&c| positions[c].iter().sum::<usize>() < total)
+                {
+                    first[1] += prob;
+                } else {
+                    let best = *children
+                        .iter()
+                        .min_by(|&&a, &&b| first_mean[a].partial_cmp(&first_mean[b]).unwrap())
+                        .unwrap();
+                    add_next_roll(&mut first, &db.first[best], prob);
+                }
+            }
+            off_mean[i] = mean(&off);
+            first_mean[i] = mean(&first);
+            db.off[i] = off;
+            db.first[i] = first;
+        }
+        db
+    }
+
+    // positions reachable by playing a single die
+    fn play_die(&self, pos: &[usize], d: usize) -> Vec<usize> {
+        let mut res = vec![];
+        let highest = (0..self.points).rev().find(|&i| pos[i] > 0);
+        let Some(highest) = highest else {
+            return vec![self.index(pos).unwrap()];
+        };
+        for i in 0..=highest {
+            // point i + 1
+            if pos[i] == 0 || (i + 1 < d && i != highest) {
+                continue;
+            }
+            let mut np = pos.to_vec();
+            np[i] -= 1;
+            if i + 1 > d {
+                np[i - d] += 1;
+            }
+            res.push(self.index(&np).unwrap());
+        }
+        res
+    }
+    pub fn points(&self) -> usize {
+        self.points
+    }
+    pub fn checkers(&self) -> usize {
+        self.checkers
+    }
+    pub fn positions(&self) -> usize {
+        self.off.len()
+    }
+
+    // `pos[i]` is the number of checkers on point i + 1
+    pub fn index(&self, pos: &[usize]) -> Option<usize> {
+        position_index(self.points, self.checkers, pos)
+    }
+    // probability of bearing off all checkers in exactly n rolls
+    pub fn rolls(&self, pos: &[usize]) -> Option<&Rolls> {
+        self.index(pos).map(|i| &self.off[i])
+    }
+    // probability of bearing off the first checker in exactly n rolls
+    pub fn first_rolls(&self, pos: &[usize]) -> Option<&Rolls> {
+        self.index(pos).map(|i| &self.first[i])
+    }
+    pub fn mean_rolls(&self, pos: &[usize]) -> Option<f64> {
+        self.rolls(pos).map(mean)
+    }
+
+    // the home board of p, if all of p's checkers left are there
+    pub fn position_of(board: &Board, p: Player) -> Option<Vec<usize>> {
+        let ps = &board.pieces;
+        if ps.bar(p) > 0 || (7..=24).any(|i| ps.point(p, i) > 0) {
+            return None;
+        }
+        Some((1..=6).map(|i| ps.point(p, i)).collect())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[self.points as u8, self.checkers as u8])?;
+        for (off, first) in self.off.iter().zip(&self.first) {
+            for p in off.iter().chain(first) {
+                w.write_all(&p.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<OneSided> {
+        OneSided::read_from(std::fs::File::open(path)?)
+    }
+    pub fn read_from<R: Read>(mut r: R) -> io::Result<OneSided> {
+        let invalid = |s: &str| io::Error::new(io::ErrorKind::InvalidData, s.to_owned());
+        let mut bytes = vec![];
+        r.read_to_end(&mut bytes)?;
+        if bytes.len() < 6 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a bearoff database"));
+        }
+        let (points, checkers) = (bytes[4] as usize, bytes[5] as usize);
+        if !(1..=6).contains(&points) || checkers > 15 {
+            return Err(invalid("bad bearoff database header"));
+        }
+        let size = compositions(checkers, points + 1);
+        if bytes.len() != 6 + size * MAX_ROLLS * 2 * 4 {
+            return Err(invalid("bad bearoff database size"));
+        }
+        let mut values = bytes[6..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        let mut read = || {
+            let mut r = [0.; MAX_ROLLS];
+            for p in r.iter_mut() {
+                *p = values.next().unwrap();
+            }
+            r
+        };
+        let mut off = Vec::with_capacity(size);
+        let mut first = Vec::with_capacity(size);
+        for _ in 0..size {
+            off.push(read());
+            first.push(read());
+        }
+        Ok(OneSided {
+            points,
+            checkers,
+            off,
+            first,
+        })
+    }
+}
+
+fn after_roll(dies: &[Vec<Vec<usize>>], i: usize, a: usize, b: usize) -> Vec<usize> {
+    let orders = if a == b {
+        vec![vec![a; 4]]
+    } else {
+        vec![vec![a, b], vec![b, a]]
+    };
+    let mut res = vec![];
+    for dice in orders {
+        let mut current = vec![i];
+        for d in dice {
+            let mut next: Vec<usize> = current
+                .iter()
+                .flat_map(|&c| dies[c][d - 1].iter().copied())
+                .collect();
+            next.sort_unstable();
+            next.dedup();
+            current = next;
+        }
+        res.extend(current);
+    }
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+fn pips(pos: &[usize]) -> usize {
+    pos.iter().enumerate().map(|(i, c)| (i + 1) * c).sum()
+}
+// P(n >= m)
+fn at_least(r: &Rolls, m: usize) -> f64 {
+    r.iter().skip(m).map(|&p| p as f64).sum()
+}
+
+// Exact cubeless evaluator for positions where both sides are bearing off. Positions
+// outside the database count as even chances, without outcomes; see `contains`.
+pub struct BearoffEvaluator {
+    pub db: OneSided,
+}
+impl BearoffEvaluator {
+    pub fn new(db: OneSided) -> BearoffEvaluator {
+        BearoffEvaluator { db }
+    }
+
+    // before the roll of the player on roll
+    pub fn probabilities(&self, board: &Board) -> Option<Probabilities> {
+        if !matches!(
+            board.state(),
+            State::ToDouble | State::ToRoll | State::Doubled
+        ) {
+            return None;
+        }
+        let x = board.on_roll();
+        let y = x.opponent();
+        let px = OneSided::position_of(board, x)?;
+        let py = OneSided::position_of(board, y)?;
+        let (ox, oy) = (self.db.rolls(&px)?, self.db.rolls(&py)?);
+        // the player on roll wins when needing no more rolls than the opponent
+        let win = (0..MAX_ROLLS).map(|n| ox[n] as f64 * at_least(oy, n)).sum();
+        let win_gammon = if board.pieces.borne_off(y) == 0 {
+            let fy = self.db.first_rolls(&py)?;
+            (0..MAX_ROLLS).map(|n| ox[n] as f64 * at_least(fy, n)).sum()
+        } else {
+            0.
+        };
+        let lose_gammon = if board.pieces.borne_off(x) == 0 {
+            let fx = self.db.first_rolls(&px)?;
+            (0..MAX_ROLLS)
+                .map(|n| oy[n] as f64 * at_least(fx, n + 1))
+                .sum()
+        } else {
+            0.
+        };
+        let p = Probabilities {
+            win,
+            win_gammon,
+            win_backgammon: 0.,
+            lose_gammon,
+            lose_backgammon: 0.,
+        };
+        Some(if x == Player::White { p } else { p.flip() })
+    }
+    pub fn contains(&self, board: &Board) -> bool {
+        [Player::White, Player::Black].iter().all(|&p| {
+            OneSided::position_of(board, p).is_some_and(|pos| self.db.index(&pos).is_some())
+        })
+    }
+
+    fn cubeless(&self, board: &Board) -> f64 {
+        self.probabilities(board)
+            .unwrap_or(Probabilities::EVEN)
+            .equity(&board.game, board.cube.value())
+    }
+    fn after(&mut self, board: &Board, act: &Action) -> f64 {
+        let mut next = board.clone();
+        next.act(act);
+        self.eval(&next).equity
+    }
+}
+
+impl Evaluator for BearoffEvaluator {
+    fn eval(&mut self, board: &Board) -> Equities {
+        match board.state() {
+            State::Init | State::MatchEnd => Equities::new(vec![], terminal_equity(board)),
+            State::End => {
+                let e = terminal_equity(board);
+                Equities::new(vec![(Action::Reset, e)], e).with_outcomes(terminal_outcomes(board))
+            }
+            State::ToDouble => {
+                let e = self.cubeless(board);
+                Equities::new(vec![(Action::NoDouble, e)], e)
+                    .with_outcomes(self.probabilities(board))
+            }
+            State::ToRoll => {
+                let e = self.cubeless(board);
+                let rolls = DiceRoll::all()
+                    .into_iter()
+                    .map(|d| (Action::Roll(d), self.after(board, &Action::Roll(d))))
+                    .collect();
+                Equities::new(rolls, e).with_outcomes(self.probabilities(board))
+            }
+            State::Doubled | State::ToMove | State::ToChoose => {
+                let eq = after_each(board.actions(), |a| self.after(board, a));
+                let e = max_eq(&eq, board.player.unwrap()).1;
+                Equities::new(eq, e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn index() {
+        let db = OneSided::generate(3, 4);
+        assert_eq!(db.positions(), binomial(7, 3));
+        for (i, p) in all_positions(3, 4).iter().enumerate() {
+            assert_eq!(db.index(p), Some(i));
+        }
+        assert_eq!(db.index(&[0, 0, 0]), Some(0));
+        assert_eq!(db.index(&[3, 1, 1]), None);
+        assert_eq!(db.index(&[0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn rolls() {
+        let db = OneSided::generate(6, 3);
+        let r = db.rolls(&[1, 0, 0, 0, 0, 0]).unwrap();
+        assert!((r[1] - 1.).abs() < 1e-6);
+        let r = db.rolls(&[2, 0, 0, 0, 0, 0]).unwrap();
+        assert!((r[1] - 1.).abs() < 1e-6);
+        // 11, 12, 21, 13, 31, 14, 41, 23 and 32 leave the checker on
+        let r = db.rolls(&[0, 0, 0, 0, 0, 1]).unwrap();
+        assert!((r[1] - 27. / 36.).abs() < 1e-6, "{:?}", r);
+        assert!((r[2] - 9. / 36.).abs() < 1e-6, "{:?}", r);
+        let m = db.mean_rolls(&[0, 0, 0, 0, 0, 3]).unwrap();
+        assert!(m > 2. && m < 3., "{}", m);
+        for r in &db.off {
+            assert!((r.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let db = OneSided::generate(4, 5);
+        let mut bytes = vec![];
+        db.write_to(&mut bytes).unwrap();
+        assert_eq!(OneSided::read_from(&bytes[..]).unwrap(), db);
+        assert!(OneSided::read_from(&b"BGB1"[..]).is_err());
+    }
+
+    #[test]
+    fn evaluator() {
+        let mut e = BearoffEvaluator::new(OneSided::generate(3, 15));
+        // White has two checkers on the 3 point and Black has all 15 at home
+        let b = Board::from_xg_id("XGID=---B------------------eee-:0:0:1:00:0:0:0:3:10").unwrap();
+        assert!(e.contains(&b));
+        let p = e.probabilities(&b).unwrap();
+        assert!((p.win - 1.).abs() < 1e-6, "{:?}", p);
+        // both dice 3 or more, or 22
+        assert!((p.win_gammon - 17. / 36.).abs() < 1e-6, "{:?}", p);
+        assert_eq!(p.lose_gammon, 0.);
+
+        // Black on roll bears off the last two checkers from the 1 point
+        let b = Board::from_xg_id("XGID=---B--------------------b-:0:0:-1:00:0:0:0:3:10").unwrap();
+        let p = e.probabilities(&b).unwrap();
+        assert!(p.win.abs() < 1e-6, "{:?}", p);
+        let eq = e.eval(&b);
+        assert!((eq.equity - p.equity(&b.game, 1)).abs() < 1e-9);
+
+        let mut b = b;
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(crate::board::Dice(2, 1)));
+        assert_eq!(e.eval(&b).actions.len(), 1);
+        assert!(!e.contains(&Board::new()));
+
+        // a contact position, as a search or a rollout may reach, is even
+        let mut b = Board::new();
+        b.act(&Action::InitRoll(crate::board::Dice(3, 1)));
+        b.act(&b.actions()[0].clone());
+        assert!(!e.contains(&b));
+        let eq = e.eval(&b);
+        assert_eq!(eq.equity, Probabilities::EVEN.equity(&b.game, 1));
+        assert_eq!(eq.outcomes, None);
+    }
+}
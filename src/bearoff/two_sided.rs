@@ -0,0 +1,415 @@
+// Two-sided bearoff database
+// Exact money equities of the player on roll for every pair of home boards,
+// by retrograde analysis from the positions with the fewest pips.
+// With at most 14 checkers left on either side there are no gammons.
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use super::{all_positions, compositions, pips, position_index, OneSided};
+use crate::board::{Action, Board, Dice, DiceRoll, Pieces, Player, State};
+use crate::cube::CubeState;
+use crate::evaluator::{after_each, max_eq, terminal_equity, Equities, Evaluator, Probabilities};
+
+const MAGIC: &[u8; 4] = b"BGB2";
+
+// where the values of each cube state are stored
+fn slot(s: CubeState) -> usize {
+    match s {
+        CubeState::Centered => 1,
+        CubeState::Owned => 2,
+        CubeState::Unavailable => 3,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoSided {
+    points: usize,
+    checkers: usize,
+    size: usize,
+    // cubeless, then no double with the cube centered, owned and unavailable
+    values: Vec<[f32; 4]>,
+}
+
+// money equity with the cube action of the player on roll, per cube value
+fn cubeful(v: &[f32; 4], s: CubeState) -> f32 {
+    let no_double = v[slot(s)];
+    if s == CubeState::Unavailable {
+        return no_double;
+    }
+    let double = (2. * v[slot(CubeState::Unavailable)]).min(1.);
+    no_double.max(double)
+}
+
+impl TwoSided {
+    pub fn generate(points: usize, checkers: usize) -> TwoSided {
+        assert!((1..=6).contains(&points) && checkers < Pieces::MAX_PIECES);
+        let positions = all_positions(points, checkers);
+        let size = positions.len();
+        // the positions after each roll, by the rules engine
+        let children: Vec<Vec<Vec<usize>>> = positions
+            .iter()
+            .map(|pos| {
+                DiceRoll::all()
+                    .into_iter()
+                    .map(|d| after_roll(points, checkers, pos, d))
+                    .collect()
+            })
+            .collect();
+        let probs: Vec<f32> = DiceRoll::all_with_prob()
+            .into_iter()
+            .map(|(_, p)| p as f32)
+            .collect();
+
+        let mut db = TwoSided {
+            points,
+            checkers,
+            size,
+            values: vec![[0.; 4]; size * size],
+        };
+        let mut order: Vec<(usize, usize)> = (0..size)
+            .flat_map(|x| (0..size).map(move |y| (x, y)))
+            .collect();
+        order.sort_by_key(|&(x, y)| pips(&positions[x]) + pips(&positions[y]));
+        let states = [
+            CubeState::Centered,
+            CubeState::Owned,
+            CubeState::Unavailable,
+        ];
+        for (x, y) in order {
+            let i = x * size + y;
+            if positions[x].iter().sum::<usize>() == 0 {
+                db.values[i] = [1.; 4];
+                continue;
+            }
+            if positions[y].iter().sum::<usize>() == 0 {
+                db.values[i] = [-1.; 4];
+                continue;
+            }
+            let mut v = [0.; 4];
+            for (roll, prob) in children[x].iter().zip(&probs) {
+                let mut best = [f32::MIN; 4];
+                for &c in roll {
+                    let after = &db.values[y * size + c];
+                    let finished = positions[c].iter().sum::<usize>() == 0;
+                    let value = |k: usize| -> f32 {
+                        if finished {
+                            1.
+                        } else if k == 0 {
+                            -after[0]
+                        } else {
+                            -cubeful(after, states[k - 1].opponent())
+                        }
+                    };
+                    for (k, b) in best.iter_mut().enumerate() {
+                        *b = b.max(value(k));
+                    }
+                }
+                for (v, b) in v.iter_mut().zip(best) {
+                    *v += prob * b;
+                }
+            }
+            db.values[i] = v;
+        }
+        db
+    }
+
+    pub fn points(&self) -> usize {
+        self.points
+    }
+    pub fn checkers(&self) -> usize {
+        self.checkers
+    }
+
+    fn get(&self, on_roll: &[usize], opponent: &[usize]) -> Option<&[f32; 4]> {
+        let x = position_index(self.points, self.checkers, on_roll)?;
+        let y = position_index(self.points, self.checkers, opponent)?;
+        Some(&self.values[x * self.size + y])
+    }
+    // money equity of the player on roll per cube value, without the cube
+    pub fn cubeless(&self, on_roll: &[usize], opponent: &[usize]) -> Option<f64> {
+        self.get(on_roll, opponent).map(|v| v[0] as f64)
+    }
+    // with the cube, when the player on roll does not double now
+    pub fn no_double(&self, on_roll: &[usize], opponent: &[usize], s: CubeState) -> Option<f64> {
+        self.get(on_roll, opponent).map(|v| v[slot(s)] as f64)
+    }
+    // with the cube and the best cube action of the player on roll
+    pub fn cubeful(&self, on_roll: &[usize], opponent: &[usize], s: CubeState) -> Option<f64> {
+        self.get(on_roll, opponent).map(|v| cubeful(v, s) as f64)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[self.points as u8, self.checkers as u8])?;
+        for v in self.values.iter().flatten() {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<TwoSided> {
+        TwoSided::read_from(std::fs::File::open(path)?)
+    }
+    pub fn read_from<R: Read>(mut r: R) -> io::Result<TwoSided> {
+        let invalid = |s: &str| io::Error::new(io::ErrorKind::InvalidData, s.to_owned());
+        let mut bytes = vec![];
+        r.read_to_end(&mut bytes)?;
+        if bytes.len() < 6 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a two-sided bearoff database"));
+        }
+        let (points, checkers) = (bytes[4] as usize, bytes[5] as usize);
+        if !(1..=6).contains(&points) || checkers >= Pieces::MAX_PIECES {
+            return Err(invalid("bad bearoff database header"));
+        }
+        let size = compositions(checkers, points + 1);
+        if bytes.len() != 6 + size * size * 4 * 4 {
+            return Err(invalid("bad bearoff database size"));
+        }
+        let values = bytes[6..]
+            .chunks(16)
+            .map(|b| {
+                let mut v = [0.; 4];
+                for (x, c) in v.iter_mut().zip(b.chunks(4)) {
+                    *x = f32::from_le_bytes([c[0], c[1], c[2], c[3]]);
+                }
+                v
+            })
+            .collect();
+        Ok(TwoSided {
+            points,
+            checkers,
+            size,
+            values,
+        })
+    }
+}
+
+// the distinct positions White can reach from `pos` with the roll
+fn after_roll(points: usize, checkers: usize, pos: &[usize], d: Dice) -> Vec<usize> {
+    let mut pieces = Pieces::empty();
+    for (i, &c) in pos.iter().enumerate() {
+        pieces.set(i + 1, Player::White, c);
+    }
+    let total: usize = pos.iter().sum();
+    pieces.set(Pieces::GOAL, Player::White, Pieces::MAX_PIECES - total);
+    // Black waits on its 1 point so that the game is not over
+    pieces.set(24, Player::Black, Pieces::MAX_PIECES);
+    let board = Board {
+        pieces,
+        dice: DiceRoll::roll(d.0, d.1),
+        player: Some(Player::White),
+        ..Board::new()
+    };
+    if total == 0 {
+        return vec![position_index(points, checkers, pos).unwrap()];
+    }
+    let mut res: Vec<usize> = board
+        .moves()
+        .into_iter()
+        .map(|m| {
+            let mut b = board.clone();
+            b.act(&Action::Move(m));
+            let pos: Vec<usize> = (1..=points)
+                .map(|i| b.pieces.point(Player::White, i))
+                .collect();
+            position_index(points, checkers, &pos).unwrap()
+        })
+        .collect();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+// Exact evaluator for short bearoffs. Money games use the cubeful equities,
+// matches the cubeless winning chances. Positions outside the database count as even,
+// with no equity for money; see `contains`.
+pub struct TwoSidedEvaluator {
+    pub db: TwoSided,
+}
+impl TwoSidedEvaluator {
+    pub fn new(db: TwoSided) -> TwoSidedEvaluator {
+        TwoSidedEvaluator { db }
+    }
+
+    fn positions(&self, board: &Board) -> Option<(Vec<usize>, Vec<usize>)> {
+        let x = board.on_roll();
+        let px = OneSided::position_of(board, x)?;
+        let py = OneSided::position_of(board, x.opponent())?;
+        self.db.get(&px, &py)?;
+        Some((px, py))
+    }
+    pub fn contains(&self, board: &Board) -> bool {
+        self.positions(board).is_some()
+    }
+
+    // before the roll of the player on roll
+    pub fn probabilities(&self, board: &Board) -> Option<Probabilities> {
+        if !matches!(
+            board.state(),
+            State::ToDouble | State::ToRoll | State::Doubled
+        ) {
+            return None;
+        }
+        let (px, py) = self.positions(board)?;
+        let win = (self.db.cubeless(&px, &py)? + 1.) / 2.;
+        let p = Probabilities {
+            win,
+            win_gammon: 0.,
+            win_backgammon: 0.,
+            lose_gammon: 0.,
+            lose_backgammon: 0.,
+        };
+        Some(if board.on_roll() == Player::White {
+            p
+        } else {
+            p.flip()
+        })
+    }
+
+    // White's equity of the player on roll's equity `e` per cube value
+    fn white(&self, board: &Board, e: f64) -> f64 {
+        let sign = if board.on_roll() == Player::White {
+            1.
+        } else {
+            -1.
+        };
+        sign * e * board.cube.value() as f64
+    }
+    fn cubeless(&self, board: &Board) -> f64 {
+        self.probabilities(board)
+            .unwrap_or(Probabilities::EVEN)
+            .equity(&board.game, board.cube.value())
+    }
+    fn after(&mut self, board: &Board, act: &Action) -> f64 {
+        let mut next = board.clone();
+        next.act(act);
+        self.eval(&next).equity
+    }
+}
+
+impl Evaluator for TwoSidedEvaluator {
+    fn eval(&mut self, board: &Board) -> Equities {
+        let money = board.game.is_money();
+        let values = || {
+            self.positions(board)
+                .map_or([0.; 4], |(px, py)| *self.db.get(&px, &py).unwrap())
+        };
+        match board.state() {
+            State::Init | State::MatchEnd => Equities::new(vec![], terminal_equity(board)),
+            State::End => {
+                let e = terminal_equity(board);
+                Equities::new(vec![(Action::Reset, e)], e)
+            }
+            State::ToDouble if money => {
+                let v = values();
+                let s = CubeState::of(board);
+                let mut eq = vec![(Action::NoDouble, self.white(board, v[slot(s)] as f64))];
+                // outside the database the game would go on without end
+                if board.can_double() && self.contains(board) {
+                    eq.push((Action::Double, self.after(board, &Action::Double)));
+                }
+                let e = max_eq(&eq, board.player.unwrap()).1;
+                Equities::new(eq, e)
+            }
+            State::ToDouble => {
+                let e = self.cubeless(board);
+                Equities::new(vec![(Action::NoDouble, e)], e)
+            }
+            State::ToRoll => {
+                let e = if money {
+                    let v = values();
+                    self.white(board, v[slot(CubeState::of(board))] as f64)
+                } else {
+                    self.cubeless(board)
+                };
+                let rolls = DiceRoll::all()
+                    .into_iter()
+                    .map(|d| (Action::Roll(d), self.after(board, &Action::Roll(d))))
+                    .collect();
+                Equities::new(rolls, e)
+            }
+            State::Doubled | State::ToMove | State::ToChoose => {
+                let eq = after_each(board.actions(), |a| self.after(board, a));
+                let e = max_eq(&eq, board.player.unwrap()).1;
+                Equities::new(eq, e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bearoff::BearoffEvaluator;
+
+    #[test]
+    fn values() {
+        let db = TwoSided::generate(4, 3);
+        let c = CubeState::Centered;
+        let close = |a: Option<f64>, b: f64| (a.unwrap() - b).abs() < 1e-6;
+        assert!(close(db.cubeless(&[1, 0, 0, 0], &[0, 0, 0, 3]), 1.));
+        // one checker on the 4 point misses only with 21
+        let e = db.cubeless(&[0, 0, 0, 1], &[1, 0, 0, 0]).unwrap();
+        assert!(close(Some(e), 32. / 36.), "{}", e);
+        assert!(close(db.no_double(&[0, 0, 0, 1], &[1, 0, 0, 0], c), e));
+        // double and pass
+        assert!(close(db.cubeful(&[0, 0, 0, 1], &[1, 0, 0, 0], c), 1.));
+
+        for x in all_positions(4, 3) {
+            for y in all_positions(4, 3) {
+                let v = |s| db.cubeful(&x, &y, s).unwrap();
+                assert!(v(CubeState::Owned) >= v(CubeState::Centered) - 1e-6);
+                assert!(v(CubeState::Centered) >= v(CubeState::Unavailable) - 1e-6);
+            }
+        }
+        assert_eq!(db.cubeless(&[0, 0, 0, 4], &[0; 4]), None);
+    }
+
+    #[test]
+    fn one_sided() {
+        let two = TwoSided::generate(4, 3);
+        let one = BearoffEvaluator::new(OneSided::generate(4, 3));
+        let b = Board::from_xg_id("XGID=-A-A------------------a-a-:0:0:1:00:0:0:0:3:10").unwrap();
+        let (px, py) = (vec![1, 0, 1, 0], vec![1, 0, 1, 0]);
+        let win = (two.cubeless(&px, &py).unwrap() + 1.) / 2.;
+        let approx = one.probabilities(&b).unwrap().win;
+        assert!((win - approx).abs() < 0.01, "{} {}", win, approx);
+        let e = TwoSidedEvaluator::new(two);
+        assert!((e.probabilities(&b).unwrap().win - win).abs() < 1e-9);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let db = TwoSided::generate(3, 3);
+        let mut bytes = vec![];
+        db.write_to(&mut bytes).unwrap();
+        assert_eq!(TwoSided::read_from(&bytes[..]).unwrap(), db);
+    }
+
+    #[test]
+    fn money_cube() {
+        let mut e = TwoSidedEvaluator::new(TwoSided::generate(4, 3));
+        // Black on roll with one checker on the 4 point against White's one on the 1 point
+        let b = Board::from_xg_id("XGID=-A-------------------a----:0:0:-1:00:0:0:0:0:10").unwrap();
+        let eq = e.eval(&b);
+        assert_eq!(eq.actions[1].0, Action::Double);
+        assert!((eq.equity + 1.).abs() < 1e-6, "{:?}", eq);
+
+        let mut b = b;
+        b.act(&Action::Double);
+        let eq = e.eval(&b);
+        assert_eq!(max_eq(&eq.actions, Player::White).0, Action::Pass);
+
+        // a contact position, as a search or a rollout may reach, is even
+        let mut b = Board::new();
+        b.game = crate::board::Match::money(false, false);
+        b.act(&Action::InitRoll(Dice(3, 1)));
+        b.act(&b.actions()[0].clone());
+        assert!(!e.contains(&b));
+        assert_eq!(e.eval(&b).equity, 0.);
+    }
+}
@@ -1,3 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+mod gnubg;
+mod mat;
+mod notation;
+mod rules;
+mod xg;
+pub use gnubg::GnubgIdError;
+pub use mat::{MatError, Transcript};
+pub use notation::MoveError;
+pub use rules::{AceyDeucey, Backgammon, Fevga, LongNardy, Plakoto, Rules, Tavla};
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Player {
     White,
@@ -15,41 +27,142 @@ impl Player {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 struct Piece(isize);
 
+// the starting position, the number of checkers and the rules the game is played by
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    // two more back checkers, from the 13 and 6 points
+    Nackgammon,
+    // 1 to 3 checkers each, on the 24, 23 and 22 points
+    Hypergammon(usize),
+    // Turkish: the standard game without the cube, and no backgammons
+    Tavla,
+    // Greek: all checkers start on the 24 point, and a blot is pinned rather than hit
+    Plakoto,
+    // Greek: both sides move the same way round the board from opposite corners
+    Fevga,
+    // Russian: as Fevga, with one checker a turn leaving the start
+    LongNardy,
+    // all checkers start off the board, and a 1-2 is followed by a double of choice
+    AceyDeucey,
+}
+impl Variant {
+    pub fn checkers(&self) -> usize {
+        match self {
+            Variant::Hypergammon(n) => *n,
+            _ => Pieces::MAX_PIECES,
+        }
+    }
+    pub fn rules(&self) -> &'static dyn Rules {
+        match self {
+            Variant::Standard | Variant::Nackgammon | Variant::Hypergammon(_) => &Backgammon,
+            Variant::Tavla => &Tavla,
+            Variant::Plakoto => &Plakoto,
+            Variant::Fevga => &Fevga,
+            Variant::LongNardy => &LongNardy,
+            Variant::AceyDeucey => &AceyDeucey,
+        }
+    }
+    // (point, checkers) for each side, in its own numbering
+    fn setup(&self) -> Vec<(usize, usize)> {
+        match self {
+            Variant::Standard | Variant::Tavla => vec![(24, 2), (13, 5), (8, 3), (6, 5)],
+            Variant::Nackgammon => vec![(24, 2), (23, 2), (13, 4), (8, 3), (6, 4)],
+            Variant::Hypergammon(n) => (0..*n).map(|i| (24 - i, 1)).collect(),
+            Variant::Plakoto | Variant::Fevga | Variant::LongNardy => vec![(24, 15)],
+            Variant::AceyDeucey => vec![(Pieces::START, 15)],
+        }
+    }
+    pub fn pieces(&self) -> Pieces {
+        let mut ps = Pieces::empty();
+        for p in [Player::White, Player::Black] {
+            let mut own = Pieces::empty();
+            for (i, c) in self.setup() {
+                own.set(i, p, c);
+            }
+            let own = self.rules().view(&own, p);
+            for i in (1..Pieces::BAR).chain([Pieces::START, Pieces::BLACK_START]) {
+                if let Some((_, c)) = own.get(i) {
+                    ps.set(i, p, c);
+                }
+            }
+        }
+        ps
+    }
+    // the extra XGID field, empty for the standard game
+    fn code(&self) -> String {
+        match self {
+            Variant::Standard => String::new(),
+            Variant::Nackgammon => "N".to_owned(),
+            Variant::Hypergammon(n) => format!("H{}", n),
+            Variant::Tavla => "T".to_owned(),
+            Variant::Plakoto => "P".to_owned(),
+            Variant::Fevga => "F".to_owned(),
+            Variant::LongNardy => "L".to_owned(),
+            Variant::AceyDeucey => "A".to_owned(),
+        }
+    }
+    fn from_code(s: &str) -> Option<Variant> {
+        match s {
+            "" => Some(Variant::Standard),
+            "N" => Some(Variant::Nackgammon),
+            "H1" => Some(Variant::Hypergammon(1)),
+            "H2" => Some(Variant::Hypergammon(2)),
+            "H3" => Some(Variant::Hypergammon(3)),
+            "T" => Some(Variant::Tavla),
+            "P" => Some(Variant::Plakoto),
+            "F" => Some(Variant::Fevga),
+            "L" => Some(Variant::LongNardy),
+            "A" => Some(Variant::AceyDeucey),
+            _ => None,
+        }
+    }
+}
+
+// the checkers of each slot, and a bit for each point holding a checker pinned under
+// the other side's (Plakoto)
 #[derive(Debug, PartialEq, Eq, Clone)]
-pub struct Pieces(Vec<Piece>);
+pub struct Pieces(Vec<Piece>, u32);
 impl Pieces {
-    const BOARD_SIZE: usize = 26;
-    const INNER_BOARD: usize = 6;
-    const BAR: usize = 25;
-    const GOAL: usize = 0;
-    const BLACK_GOAL: usize = 26;
-    const BLACK_BAR: usize = 27;
-    const MAX_PIECES: usize = 15;
+    pub(crate) const BOARD_SIZE: usize = 26;
+    pub(crate) const INNER_BOARD: usize = 6;
+    pub(crate) const BAR: usize = 25;
+    pub(crate) const GOAL: usize = 0;
+    pub(crate) const BLACK_GOAL: usize = 26;
+    pub(crate) const BLACK_BAR: usize = 27;
+    pub(crate) const MAX_PIECES: usize = 15;
+    // checkers yet to enter the board (acey-deucey)
+    pub(crate) const START: usize = 28;
+    pub(crate) const BLACK_START: usize = 29;
 
-    fn empty() -> Pieces {
-        Pieces(vec![Piece(0); Pieces::BOARD_SIZE + 2])
+    pub(crate) fn empty() -> Pieces {
+        Pieces(vec![Piece(0); Pieces::BLACK_START + 1], 0)
     }
-    fn new() -> Pieces {
-        let p = vec![
-            0_, -2, 0, 0, 0, 0, 5_, 0, 3, 0, 0, 0, -5_, 5, 0, 0, 0, -3, 0_, -5, 0, 0, 0, 0, 2_, 0,
-            0, 0,
-        ];
-        Pieces(p.into_iter().map(Piece).collect::<Vec<_>>())
+    pub fn new() -> Pieces {
+        Variant::Standard.pieces()
     }
 
-    fn reverse(&self) -> Pieces {
-        let mut p = vec![Piece(0); Pieces::BOARD_SIZE + 2];
-        for i in 0..Pieces::BOARD_SIZE {
-            p[i] = self.0[Pieces::BOARD_SIZE - i - 1];
+    // the position with slot i moved to f(i), the goals and bars of the sides swapped
+    fn remap(&self, f: impl Fn(usize) -> usize) -> Pieces {
+        let mut p = Pieces::empty();
+        for i in 1..Pieces::BAR {
+            p.0[f(i)] = self.0[i];
+            p.set_pinned(f(i), self.pinned(i));
         }
-        p[Pieces::BAR] = self.0[Pieces::BLACK_BAR];
-        p[Pieces::GOAL] = self.0[Pieces::BLACK_GOAL];
-        p[Pieces::BLACK_BAR] = self.0[Pieces::BAR];
-        p[Pieces::BLACK_GOAL] = self.0[Pieces::GOAL];
-
-        Pieces(p)
+        p.0[Pieces::BAR] = self.0[Pieces::BLACK_BAR];
+        p.0[Pieces::GOAL] = self.0[Pieces::BLACK_GOAL];
+        p.0[Pieces::BLACK_BAR] = self.0[Pieces::BAR];
+        p.0[Pieces::BLACK_GOAL] = self.0[Pieces::GOAL];
+        p.0[Pieces::START] = self.0[Pieces::BLACK_START];
+        p.0[Pieces::BLACK_START] = self.0[Pieces::START];
+        p
     }
-    fn reversed(&self, p: Player) -> Pieces {
+    fn reverse(&self) -> Pieces {
+        self.remap(|i| Pieces::BAR - i)
+    }
+    // the position seen from p: p's bar is 25 and p's goal is 0
+    pub(crate) fn reversed(&self, p: Player) -> Pieces {
         if p == Player::White {
             self.clone()
         } else {
@@ -57,7 +170,8 @@ impl Pieces {
         }
     }
 
-    fn get(&self, i: usize) -> Option<(Player, usize)> {
+    // owner and count of the checkers at an index, in White's numbering
+    pub fn get(&self, i: usize) -> Option<(Player, usize)> {
         let p = self.0[i];
         if p.0 > 0 {
             Some((Player::White, p.0 as usize))
@@ -67,7 +181,7 @@ impl Pieces {
             None
         }
     }
-    fn set(&mut self, i: usize, p: Player, c: usize) {
+    pub(crate) fn set(&mut self, i: usize, p: Player, c: usize) {
         if c == 0 {
             self.0[i] = Piece(0);
         } else if p == Player::White {
@@ -76,6 +190,30 @@ impl Pieces {
             self.0[i] = Piece(-(c as isize))
         }
     }
+    // checkers of p at an index, in the numbering the position is seen in
+    fn count(&self, i: usize, p: Player) -> usize {
+        match self.get(i) {
+            Some((o, c)) if o == p => c,
+            _ => 0,
+        }
+    }
+    // where a checker of p played from an index comes from: checkers yet to enter come
+    // in like those on the bar, once the bar is empty
+    fn source(&self, from: usize, p: Player) -> usize {
+        let waiting = self.count(Pieces::BAR, p) == 0 && self.count(Pieces::START, p) > 0;
+        if from == Pieces::BAR && waiting {
+            Pieces::START
+        } else {
+            from
+        }
+    }
+    pub fn pinned(&self, i: usize) -> bool {
+        self.1 >> i & 1 == 1
+    }
+    fn set_pinned(&mut self, i: usize, pinned: bool) {
+        let bit = 1 << i;
+        self.1 = if pinned { self.1 | bit } else { self.1 & !bit };
+    }
     fn add(&mut self, i: usize, p: Player, d: isize) {
         if p == Player::White {
             self.0[i] = Piece(self.0[i].0 + d)
@@ -127,70 +265,94 @@ impl Pieces {
         self.add(from, player, -1);
         self.add(to, player, 1);
     }
+    // the farthest point of p from home, counting its pinned checkers and those yet to enter
     fn backman(&self, p: Player) -> usize {
+        if self.count(Pieces::START, p) > 0 {
+            return Pieces::START;
+        }
         for i in (0..=Pieces::BAR).rev() {
-            if let Some((o, _)) = self.get(i) {
-                if o == p {
-                    return i;
-                }
+            match self.get(i) {
+                Some((o, _)) if o == p => return i,
+                Some(_) if self.pinned(i) => return i,
+                _ => {}
             }
         }
         panic!("no pieces")
     }
-    fn listup(&self, dice: &[usize], p: Player) -> Vec<Move> {
-        let pieces = self;
-        if dice.len() == 0 || self.backman(p) == 0 {
-            return vec![Move(vec![])];
-        }
-        let (d, dice) = dice.split_at(1);
-        let mut d = d[0];
-        let mut mov = vec![];
-        for i in (1..=Pieces::BOARD_SIZE).rev() {
-            let backman = pieces.backman(p);
-            // move from bar
-            if backman == 0 && !pieces.movable(0, d, p) {
-                continue;
-            }
-            // backman can be bearoff over rolled
-            if i == backman && backman <= Pieces::INNER_BOARD && i < d {
-                d = backman;
-            }
-            // bareoff is not allowed if backman dose not reached
-            if backman > Pieces::INNER_BOARD && i == d {
-                continue;
-            }
-            // too big move
-            if i < d {
-                continue;
-            }
-            if pieces.movable(i, i - d, p) {
-                let mut np = pieces.clone();
-                np.mov(i, i - d, p);
-                for mut m in np.listup(dice, p) {
-                    m.0.insert(0, (i, i - d, pieces.hittable(i - d, p)));
-                    mov.push(m);
-                }
-            }
+    /// Checkers of `p` on point `i`, numbered from `p`'s side.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `i` is a point from 1 to 24.
+    pub fn point(&self, p: Player, i: usize) -> usize {
+        assert!((1..Pieces::BAR).contains(&i));
+        match self.reversed(p).get(i) {
+            Some((o, c)) if o == p => c,
+            _ => 0,
         }
-        mov
     }
+    /// Checkers of `p` on the bar.
+    pub fn bar(&self, p: Player) -> usize {
+        let i = match p {
+            Player::White => Pieces::BAR,
+            Player::Black => Pieces::BLACK_BAR,
+        };
+        self.get(i).map_or(0, |(_, c)| c)
+    }
+    /// Checkers `p` has borne off.
+    pub fn borne_off(&self, p: Player) -> usize {
+        let i = match p {
+            Player::White => Pieces::GOAL,
+            Player::Black => Pieces::BLACK_GOAL,
+        };
+        self.get(i).map_or(0, |(_, c)| c)
+    }
+    /// The pip count of `p`.
+    pub fn pips(&self, p: Player) -> usize {
+        let ps = self.reversed(p);
+        let waiting = Pieces::BAR * ps.count(Pieces::START, p);
+        (1..=Pieces::BAR)
+            .filter_map(|i| match ps.get(i) {
+                Some((o, c)) if o == p => Some(i * c),
+                _ => None,
+            })
+            .sum::<usize>()
+            + waiting
+    }
+    /// Points where `p` has a single checker.
+    pub fn blots(&self, p: Player) -> usize {
+        (1..Pieces::BAR)
+            .filter(|&i| self.get(i) == Some((p, 1)))
+            .count()
+    }
+    // points won by p: 0 until all of p's checkers are off, 1 if the opponent has borne
+    // one off, 3 with an opponent checker on the bar, yet to enter or in p's home board, and
+    // 2 otherwise
     fn goal(&self, p: Player) -> usize {
         let ps = self.reversed(p);
         if ps.backman(p) > 0 {
             return 0;
         }
-        if ps.get(Pieces::GOAL).is_some() {
+        let o = p.opponent();
+        if ps.get(Pieces::BLACK_GOAL).is_some() {
             return 1;
         }
-        let backman = ps.backman(p);
-        if backman < 19 {
-            2
-        } else {
+        let back = (1..=Pieces::INNER_BOARD)
+            .chain([Pieces::BLACK_BAR, Pieces::BLACK_START])
+            .any(|i| matches!(ps.get(i), Some((q, _)) if q == o));
+        if back {
             3
+        } else {
+            2
         }
     }
 }
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+impl Default for Pieces {
+    fn default() -> Self {
+        Pieces::new()
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct Dice(pub usize, pub usize);
 impl Dice {
     pub fn prob(&self) -> f64 {
@@ -204,12 +366,16 @@ impl Dice {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct DiceRoll(Option<Dice>);
 impl DiceRoll {
-    fn new() -> DiceRoll {
+    pub fn new() -> DiceRoll {
         DiceRoll(None)
     }
-    fn roll(x: usize, y: usize) -> DiceRoll {
+    pub fn roll(x: usize, y: usize) -> DiceRoll {
         DiceRoll(Some(Dice(x, y)))
     }
+    /// The dice to play, if rolled.
+    pub fn dice(&self) -> Option<Dice> {
+        self.0
+    }
     fn init_player(&self) -> Option<Player> {
         match self.0 {
             None => None,
@@ -240,6 +406,17 @@ impl DiceRoll {
         }
         v
     }
+    pub fn all_init() -> Vec<Dice> {
+        let mut v = vec![];
+        for x in 1..=6 {
+            for y in 1..=6 {
+                if x != y {
+                    v.push(Dice(x, y));
+                }
+            }
+        }
+        v
+    }
     pub fn all_with_prob() -> Vec<(Dice, f64)> {
         let mut v = vec![];
         for x in 1..=6 {
@@ -249,72 +426,119 @@ impl DiceRoll {
         }
         v
     }
-    fn to_str(&self) -> String {
+    fn to_str(self) -> String {
         match self.0 {
             None => "-".to_string(),
             Some(Dice(x, y)) => format!("{}{}", x, y),
         }
     }
 }
+impl Default for DiceRoll {
+    fn default() -> Self {
+        DiceRoll::new()
+    }
+}
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Cube {
-    position: Option<Player>,
-    level: usize,
-    doubled: bool,
-    max_level: usize,
+    // the player who may redouble, None when centered
+    pub(crate) position: Option<Player>,
+    // the cube value is 2^level
+    pub(crate) level: usize,
+    // offered and waiting for take or pass
+    pub(crate) doubled: bool,
+    pub(crate) max_level: usize,
+    // redoubles on the spot while doubled: 1 after a beaver, 2 after a raccoon
+    pub(crate) beavers: usize,
 }
 impl Cube {
-    const CENTER_INIT: Cube = Cube {
+    pub const CENTER_INIT: Cube = Cube {
         position: None,
         level: 0,
         doubled: false,
         max_level: Cube::DEFAULT_MAX_LEVEL,
+        beavers: 0,
     };
-    const DEFAULT_MAX_LEVEL: usize = 10;
+    pub const DEFAULT_MAX_LEVEL: usize = 10;
 
     fn double(&self, p: Player) -> Cube {
         Cube {
             position: Some(p.opponent()),
             level: self.level,
             doubled: true,
-            max_level: 10,
+            max_level: self.max_level,
+            beavers: 0,
+        }
+    }
+    // the player p who was doubled redoubles at once and keeps the cube
+    fn redouble(&self, p: Player) -> Cube {
+        Cube {
+            position: Some(p),
+            level: self.level + 1,
+            beavers: self.beavers + 1,
+            ..*self
         }
     }
     fn reach_max(self) -> bool {
         self.level >= self.max_level
     }
     fn take(&self) -> Cube {
-        let mut cube = self.clone();
+        let mut cube = *self;
         cube.doubled = false;
         cube.level += 1;
+        cube.beavers = 0;
         cube
     }
-    fn value(&self) -> usize {
+    /// The value of the cube: 1, 2, 4, ...
+    pub fn value(&self) -> usize {
         1 << self.level
     }
+    /// The player who may double next, `None` while the cube is centered.
+    pub fn owner(&self) -> Option<Player> {
+        self.position
+    }
 }
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Match {
     pub score: (usize, usize),
+    // 0 for an unlimited money session
     pub length: usize,
     pub crawford: bool,
+    // money session options
+    pub jacoby: bool,
+    pub beaver: bool,
 }
 impl Match {
     fn single() -> Match {
+        Match::with_length(1)
+    }
+    pub fn with_length(length: usize) -> Match {
         Match {
             score: (0, 0),
-            length: 1,
+            length,
             crawford: false,
+            jacoby: false,
+            beaver: false,
         }
     }
-    fn with_length(length: usize) -> Match {
+    pub fn money(jacoby: bool, beaver: bool) -> Match {
         Match {
-            score: (0, 0),
-            length,
-            crawford: false,
+            jacoby,
+            beaver,
+            ..Match::with_length(0)
         }
     }
-    fn add_score(&mut self, player: Player, score: usize) {
+    pub fn is_money(&self) -> bool {
+        self.length == 0
+    }
+    pub fn add_score(&mut self, player: Player, score: usize) {
+        if self.is_money() {
+            if player == Player::White {
+                self.score.0 += score;
+            } else {
+                self.score.1 += score;
+            }
+            return;
+        }
         let not_reached = self.score.0 < self.length - 1 && self.score.1 < self.length - 1;
         if player == Player::White {
             self.score.0 += score;
@@ -336,7 +560,9 @@ impl Match {
         }
     }
     pub fn winner(&self) -> Option<Player> {
-        if self.score.0 >= self.length {
+        if self.is_money() {
+            None
+        } else if self.score.0 >= self.length {
             Some(Player::White)
         } else if self.score.1 >= self.length {
             Some(Player::Black)
@@ -354,11 +580,14 @@ pub struct Board {
     pub player: Option<Player>,
     pub game: Match,
     pub result: Option<Result>,
+    pub variant: Variant,
+    // acey-deucey: a 1-2 was played in full, and the double of choice comes next
+    pub bonus: bool,
 }
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Result {
-    player: Player,
-    score: usize,
+    pub player: Player,
+    pub score: usize,
 }
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum State {
@@ -367,10 +596,12 @@ pub enum State {
     ToRoll,
     Doubled,
     ToMove,
+    // acey-deucey: the double to play after a 1-2
+    ToChoose,
     End,
     MatchEnd,
 }
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum Action {
     InitRoll(Dice),
     Roll(Dice),
@@ -379,14 +610,20 @@ pub enum Action {
     Double,
     Pass,
     Take,
+    // money only: take and redouble at once keeping the cube, and the doubler's answer
+    Beaver,
+    Raccoon,
+    // acey-deucey: the double played after a 1-2
+    Choose(usize),
     Reset,
     None, // for tree search
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct Move(Vec<(usize, usize, bool)>);
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+/// `(from, to, hit)` for each die, in the mover's numbering: 25 is the bar and 0 is off.
+pub struct Move(pub Vec<(usize, usize, bool)>);
 impl Move {
-    const DANCE: Move = Move(vec![]);
+    pub const DANCE: Move = Move(vec![]);
     pub fn to_str(&self) -> String {
         let mut mov = self.0.clone();
         mov.sort_by(|(a, b, _), (c, d, _)| (-(*a as isize), b).cmp(&(-(*c as isize), d)));
@@ -395,7 +632,7 @@ impl Move {
         while i < mov.len() {
             let (from, to, hit) = mov[i];
             if i != 0 {
-                s.push_str(" ");
+                s.push(' ');
             }
             s.push_str(&format!("{}", from));
             let mut prev = to;
@@ -410,7 +647,7 @@ impl Move {
                 mov.remove(j);
                 if last_hit {
                     s.push_str(&format!("/{}", prev));
-                    s.push_str("*");
+                    s.push('*');
                 }
                 prev = to;
                 last_hit = hit;
@@ -448,7 +685,7 @@ impl Move {
                 let max_roll = use_all.clone().map(|m| m.0[0].0 - m.0[0].1).max().unwrap();
                 use_all
                     .filter(|m| m.0[0].0 - m.0[0].1 == max_roll)
-                    .map(|m| m.clone())
+                    .cloned()
                     .collect()
             } else if max_moves == 0 {
                 vec![Move::DANCE]
@@ -462,27 +699,32 @@ impl Move {
 }
 impl PartialOrd for Move {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let x = self.0.iter().map(|(a, b, _)| (-(*a as isize), b));
-        let y = other.0.iter().map(|(a, b, _)| (-(*a as isize), b));
-        Some(x.cmp(y))
+        Some(self.cmp(other))
     }
 }
 impl Ord for Move {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        let x = self.0.iter().map(|(a, b, _)| (-(*a as isize), b));
+        let y = other.0.iter().map(|(a, b, _)| (-(*a as isize), b));
+        x.cmp(y)
     }
 }
 
 impl Board {
     pub fn new() -> Board {
+        Board::with_variant(Variant::Standard)
+    }
+    pub fn with_variant(variant: Variant) -> Board {
         Board {
-            pieces: Pieces::new(),
+            pieces: variant.pieces(),
             dice: DiceRoll::new(),
             cube: Cube::CENTER_INIT,
             to_roll: false,
             player: None,
             result: None,
             game: Match::single(),
+            variant,
+            bonus: false,
         }
     }
     pub fn act(&mut self, act: &Action) {
@@ -494,17 +736,19 @@ impl Board {
             Action::Double => self.double(),
             Action::Pass => self.pass(),
             Action::Take => self.take(),
+            Action::Beaver | Action::Raccoon => self.beaver(act),
+            Action::Choose(n) => self.choose(*n),
             Action::Reset => self.reset(),
             Action::None => unreachable!(),
         }
     }
     pub fn actions(&self) -> Vec<Action> {
         match self.state() {
-            State::Init => DiceRoll::all()
+            State::Init => DiceRoll::all_init()
                 .into_iter()
-                .map(|d| Action::InitRoll(d))
+                .map(Action::InitRoll)
                 .collect(),
-            State::ToMove => self.moves().into_iter().map(|m| Action::Move(m)).collect(),
+            State::ToMove => self.moves().into_iter().map(Action::Move).collect(),
             State::ToDouble => {
                 if self.can_double() {
                     vec![Action::Double, Action::NoDouble]
@@ -512,11 +756,24 @@ impl Board {
                     vec![Action::NoDouble]
                 }
             }
-            State::ToRoll => DiceRoll::all()
-                .into_iter()
-                .map(|d| Action::InitRoll(d))
-                .collect(),
-            State::Doubled => vec![Action::Pass, Action::Take],
+            State::ToRoll => DiceRoll::all().into_iter().map(Action::Roll).collect(),
+            State::Doubled => {
+                // a beaver can't be dropped
+                let mut acts = if self.cube.beavers == 0 {
+                    vec![Action::Pass, Action::Take]
+                } else {
+                    vec![Action::Take]
+                };
+                if self.can_beaver() {
+                    acts.push(if self.cube.beavers == 0 {
+                        Action::Beaver
+                    } else {
+                        Action::Raccoon
+                    });
+                }
+                acts
+            }
+            State::ToChoose => (1..=6).map(Action::Choose).collect(),
             State::End => vec![Action::Reset],
             State::MatchEnd => vec![],
         }
@@ -531,33 +788,67 @@ impl Board {
     }
     fn act_move(&mut self, mov: &Move) {
         let p = self.player.unwrap();
-        let mut ps = self.pieces.reversed(p);
+        let rules = self.variant.rules();
+        let mut ps = rules.view(&self.pieces, p);
         for m in mov.0.iter() {
-            ps.mov(m.0, m.1, p);
+            rules.mov(&mut ps, m.0, m.1, p);
         }
-        self.pieces = ps.reversed(p);
+        self.pieces = rules.view(&ps, p);
+        let Dice(x, y) = self.dice.0.unwrap();
         self.dice = DiceRoll(None);
-        self.player = Some(p.opponent());
+        // a 1-2 played in full is followed by a double of choice, and that double played
+        // in full by another turn
+        let acey = rules.bonus() && x.min(y) == 1 && x.max(y) == 2;
+        let again = self.bonus && mov.0.len() == 4;
+        self.bonus = !self.bonus && acey && mov.0.len() == 2;
+        if !again && !self.bonus {
+            self.player = Some(p.opponent());
+        }
 
         self.check_end()
     }
-    fn moves(&self) -> Vec<Move> {
+    fn choose(&mut self, n: usize) {
+        assert_eq!(self.state(), State::ToChoose);
+        self.dice = DiceRoll::roll(n, n);
+    }
+    /// The legal moves with the dice rolled, each distinct result once.
+    pub fn moves(&self) -> Vec<Move> {
         let p = self.player.unwrap();
+        let rules = self.variant.rules();
+        let pieces = rules.view(&self.pieces, p);
         let mut moves = vec![];
-        for dice in self.dice.moves().iter_mut() {
-            let pieces = self.pieces.reversed(p);
-            let mut m = pieces.listup(dice, self.player.unwrap());
-            moves.append(&mut m);
+        for dice in self.dice.moves().iter() {
+            moves.append(&mut rules.listup(&pieces, dice, p));
+        }
+        let moves = Move::filter_moves(&Move::uniq_moves(&moves));
+        // every way to play would break a rule on the resulting position
+        if moves.is_empty() {
+            return vec![Move::DANCE];
         }
-        Move::filter_moves(&Move::uniq_moves(&moves))
+        moves
+    }
+
+    /// The match score as (White, Black).
+    pub fn score(&self) -> (usize, usize) {
+        self.game.score
     }
 
     pub fn can_double(&self) -> bool {
-        !self.game.crawford
+        self.variant.rules().cube()
+            && !self.game.crawford
             && !self.cube.reach_max()
             && (self.cube.position.is_none() || self.cube.position == self.player)
     }
 
+    // in a money game with beavers, once each way, while the final cube stays in range
+    pub fn can_beaver(&self) -> bool {
+        self.game.is_money()
+            && self.game.beaver
+            && self.cube.doubled
+            && self.cube.beavers < 2
+            && self.cube.level + 2 <= self.cube.max_level
+    }
+
     fn no_double(&mut self) {
         self.to_roll = true;
     }
@@ -580,13 +871,28 @@ impl Board {
 
     fn take(&mut self) {
         assert!(self.cube.doubled);
+        let p = self.player.unwrap();
+        // the doubler rolls, and is the one taking a beaver
+        let roller = if self.cube.beavers == 1 {
+            p
+        } else {
+            p.opponent()
+        };
         self.cube = self.cube.take();
-        self.player = Some(self.player.unwrap().opponent());
+        self.player = Some(roller);
         self.to_roll = true;
     }
 
+    fn beaver(&mut self, act: &Action) {
+        assert!(self.can_beaver());
+        assert_eq!(*act == Action::Raccoon, self.cube.beavers == 1);
+        let p = self.player.unwrap();
+        self.cube = self.cube.redouble(p);
+        self.player = Some(p.opponent());
+    }
+
     fn reset(&mut self) {
-        self.pieces = Pieces::new();
+        self.pieces = self.variant.pieces();
         self.dice = DiceRoll::new();
         self.cube = Cube::CENTER_INIT;
         self.to_roll = false;
@@ -595,9 +901,19 @@ impl Board {
     }
 
     fn check_end(&mut self) {
-        let p = self.player.unwrap();
-        let white = self.pieces.goal(Player::White);
-        let black = self.pieces.goal(Player::Black);
+        // the Jacoby rule: no gammons in money play before the first double
+        let jacoby = self.game.is_money() && self.game.jacoby && self.cube.position.is_none();
+        let rules = self.variant.rules();
+        let goal = |p| {
+            let g = rules.goal(&self.pieces, p);
+            if jacoby {
+                g.min(1)
+            } else {
+                g
+            }
+        };
+        let white = goal(Player::White);
+        let black = goal(Player::Black);
         if white > 0 {
             self.result = Some(Result {
                 player: Player::White,
@@ -615,6 +931,7 @@ impl Board {
 
     fn game_end(&mut self) {
         self.player = None;
+        self.bonus = false;
         let result = self.result.unwrap();
         self.game.add_score(result.player, result.score);
     }
@@ -629,6 +946,9 @@ impl Board {
         if self.dice.0.is_some() {
             return State::ToMove;
         }
+        if self.bonus {
+            return State::ToChoose;
+        }
         if self.cube.doubled {
             return State::Doubled;
         }
@@ -638,7 +958,7 @@ impl Board {
         if self.dice.0.is_none() && self.player.is_some() {
             return State::ToDouble;
         }
-        return State::Init;
+        State::Init
     }
 
     pub fn xgid(&self) -> String {
@@ -674,13 +994,18 @@ impl Board {
         };
         let dice = match self.state() {
             State::ToMove => self.dice.to_str(),
-            State::Doubled => "D".to_owned(),
+            State::Doubled => ["D", "B", "R"][self.cube.beavers].to_owned(),
             _ => "".to_owned(),
         };
         let white_score = self.game.score.0;
         let black_score = self.game.score.1;
 
-        let crawford = if self.game.crawford { '1' } else { '0' };
+        // the Jacoby rule and beavers of a money game, as for XG
+        let crawford = if self.game.is_money() {
+            self.game.jacoby as usize + 2 * self.game.beaver as usize
+        } else {
+            self.game.crawford as usize
+        };
         let length = self.game.length;
         let max_level = self.cube.max_level;
 
@@ -689,79 +1014,302 @@ impl Board {
             ":{}:{}:{}:{}:{}:{}:{}:{}:{}",
             level, pos, player, dice, white_score, black_score, crawford, length, max_level
         ));
+        if let Some(v) = self.variant_field() {
+            s.push(':');
+            s.push_str(&v);
+        }
 
         s
     }
-    pub fn from_xgid(id: &str) -> Board {
-        let i = id.find("=").unwrap();
+    // the extra field of both XGID formats, only when it is not the standard game:
+    // the variant code, + for a bonus turn, /n for each pinned point, and /Wn and /Bn
+    // for the checkers of each side yet to enter
+    fn variant_field(&self) -> Option<String> {
+        if self.variant == Variant::Standard {
+            return None;
+        }
+        let mut s = self.variant.code();
+        if self.bonus {
+            s.push('+');
+        }
+        for i in (1..Pieces::BAR).filter(|&i| self.pieces.pinned(i)) {
+            s.push_str(&format!("/{}", i));
+        }
+        for (side, i) in [('W', Pieces::START), ('B', Pieces::BLACK_START)] {
+            if let Some((_, c)) = self.pieces.get(i) {
+                s.push_str(&format!("/{}{}", side, c));
+            }
+        }
+        Some(s)
+    }
+    pub fn from_xgid(id: &str) -> std::result::Result<Board, XgidError> {
+        let i = id.find('=').ok_or(XgidError::MissingPrefix)?;
         let id = &id[i + 1..];
         let id: Vec<&str> = id.split(':').collect();
+        if id.len() != 10 && id.len() != 11 {
+            return Err(XgidError::FieldCount(id.len()));
+        }
+        let extra = VariantField::parse(id.get(10).copied())?;
+        let number = |field: usize| {
+            id[field]
+                .parse::<usize>()
+                .map_err(|_| XgidError::BadNumber {
+                    field,
+                    value: id[field].to_owned(),
+                })
+        };
 
+        let len = id[0].chars().count();
+        // a shorter position leaves the slots after it empty
+        if len > Pieces::BOARD_SIZE + 2 {
+            return Err(XgidError::PositionLength(len));
+        }
         let mut pieces = Pieces::empty();
-        let mut white_goal = 15;
-        let mut black_goal = 15;
-        id[0].chars().enumerate().for_each(|(i, b)| {
-            if b == '-' {
-                return;
+        let mut white = 0;
+        let mut black = 0;
+        for (i, b) in id[0].chars().enumerate() {
+            let (p, c) = match b {
+                '-' => continue,
+                'A'..='O' if i != Pieces::BLACK_BAR => {
+                    (Player::White, (b as u8 + 1 - b'A') as usize)
+                }
+                'a'..='o' if i != Pieces::BAR => (Player::Black, (b as u8 + 1 - b'a') as usize),
+                _ => return Err(XgidError::BadChecker { pos: i, found: b }),
+            };
+            // borne off checkers are derived from the rest
+            if i == Pieces::GOAL || i == Pieces::BLACK_GOAL {
+                continue;
             }
-            let (p, c) = if b.is_uppercase() {
-                let c = b as u8 + 1 - 'A' as u8;
-                white_goal -= c;
-                (Player::White, c)
+            if p == Player::White {
+                white += c;
             } else {
-                let c = b as u8 + 1 - 'a' as u8;
-                black_goal -= c;
-                (Player::Black, c)
-            };
-            pieces.set(i, p, c as usize);
-        });
-        pieces.set(Pieces::GOAL, Player::White, white_goal as usize);
-        pieces.set(Pieces::BLACK_GOAL, Player::Black, black_goal as usize);
+                black += c;
+            }
+            pieces.set(i, p, c);
+        }
+        extra.apply(&mut pieces, white, black)?;
 
-        let level: usize = id[1].parse().unwrap();
-        let max_level: usize = id[9].parse().unwrap();
+        let level = number(1)?;
+        let max_level = number(9)?;
+        if level > max_level {
+            return Err(XgidError::Inconsistent("cube level above the maximum"));
+        }
         let cube = Cube {
             level,
             max_level,
             position: match id[2] {
                 "1" => Some(Player::White),
                 "-1" => Some(Player::Black),
-                _ => None,
+                "0" => None,
+                s => return Err(XgidError::BadCubePosition(s.to_owned())),
+            },
+            doubled: ["D", "B", "R"].contains(&id[4]),
+            beavers: match id[4] {
+                "B" => 1,
+                "R" => 2,
+                _ => 0,
             },
-            doubled: id[4] == "D",
         };
 
+        // xgid() writes 0 for Black
         let player = match id[3] {
             "1" => Some(Player::White),
-            "-1" => Some(Player::Black),
-            _ => None,
+            "-1" | "0" => Some(Player::Black),
+            "" => None,
+            s => return Err(XgidError::BadPlayer(s.to_owned())),
         };
 
         let dice = match id[4] {
-            "D" => DiceRoll::new(),
-            "" => DiceRoll::new(),
-            s => DiceRoll::roll(
-                (s.chars().nth(0).unwrap() as u8 - '0' as u8) as usize,
-                (s.chars().nth(1).unwrap() as u8 - '0' as u8) as usize,
-            ),
+            "D" | "B" | "R" | "" => DiceRoll::new(),
+            s => {
+                let d: Vec<usize> = s
+                    .chars()
+                    .filter_map(|c| c.to_digit(10))
+                    .filter(|d| (1..=6).contains(d))
+                    .map(|d| d as usize)
+                    .collect();
+                if d.len() != 2 || s.len() != 2 {
+                    return Err(XgidError::InvalidDice(s.to_owned()));
+                }
+                DiceRoll::roll(d[0], d[1])
+            }
         };
+        if player.is_none() && (dice.0.is_some() || cube.doubled || extra.bonus) {
+            return Err(XgidError::Inconsistent("dice or double without a player"));
+        }
+        let length = number(8)?;
+        let flags = number(7)?;
+        if flags > if length == 0 { 3 } else { 1 } {
+            return Err(XgidError::BadNumber {
+                field: 7,
+                value: id[7].to_owned(),
+            });
+        }
         let game = Match {
-            score: (id[5].parse().unwrap(), id[6].parse().unwrap()),
-            crawford: id[7] == "1",
-            length: id[8].parse().unwrap(),
+            score: (number(5)?, number(6)?),
+            ..if length == 0 {
+                Match::money(flags & 1 == 1, flags & 2 == 2)
+            } else {
+                Match {
+                    crawford: flags == 1,
+                    ..Match::with_length(length)
+                }
+            }
         };
+        if !game.is_money() && (game.score.0 > game.length || game.score.1 > game.length) {
+            return Err(XgidError::Inconsistent("score above the match length"));
+        }
+        if cube.beavers > 0 && !game.beaver {
+            return Err(XgidError::Inconsistent("beaver without the beaver rule"));
+        }
 
-        Board {
+        Ok(Board {
             pieces,
             cube,
             player,
-            to_roll: cube.position == player.map(|p| p.opponent()),
+            to_roll: !cube.doubled && cube.position == player.map(|p| p.opponent()),
             dice,
             game,
             result: None,
+            variant: extra.variant,
+            bonus: extra.bonus,
+        })
+    }
+}
+impl std::str::FromStr for Board {
+    type Err = XgidError;
+    fn from_str(s: &str) -> std::result::Result<Board, XgidError> {
+        Board::from_xgid(s)
+    }
+}
+// what the extra XGID field holds besides the checkers on the board
+struct VariantField {
+    variant: Variant,
+    bonus: bool,
+    pins: Vec<usize>,
+    // the checkers of White and Black yet to enter
+    waiting: [usize; 2],
+}
+impl VariantField {
+    fn parse(field: Option<&str>) -> std::result::Result<VariantField, XgidError> {
+        let mut v = VariantField {
+            variant: Variant::Standard,
+            bonus: false,
+            pins: vec![],
+            waiting: [0, 0],
+        };
+        let Some(s) = field else {
+            return Ok(v);
+        };
+        let bad = || XgidError::BadVariant(s.to_string());
+        let mut fields = s.split('/');
+        let code = fields.next().unwrap();
+        (v.variant, v.bonus) = match code.strip_suffix('+') {
+            Some("A") => (Variant::AceyDeucey, true),
+            Some(_) => return Err(bad()),
+            None => (Variant::from_code(code).ok_or_else(bad)?, false),
+        };
+        for f in fields {
+            let (side, n) = match f.strip_prefix('W') {
+                Some(n) => (Some(0), n),
+                None => f.strip_prefix('B').map_or((None, f), |n| (Some(1), n)),
+            };
+            match (side, n.parse::<usize>()) {
+                (None, Ok(i)) if v.variant == Variant::Plakoto && (1..Pieces::BAR).contains(&i) => {
+                    v.pins.push(i)
+                }
+                (Some(side), Ok(n)) if v.variant == Variant::AceyDeucey && n > 0 => {
+                    v.waiting[side] = n
+                }
+                _ => return Err(bad()),
+            }
+        }
+        Ok(v)
+    }
+
+    // pins the points, puts the waiting checkers at the start and bears off the
+    // checkers of each side not counted on the board
+    fn apply(
+        &self,
+        pieces: &mut Pieces,
+        mut white: usize,
+        mut black: usize,
+    ) -> std::result::Result<(), XgidError> {
+        // a pinned checker belongs to the other side of the one on top of it
+        for &i in &self.pins {
+            match pieces.get(i) {
+                Some((Player::White, _)) => black += 1,
+                Some((Player::Black, _)) => white += 1,
+                None => return Err(XgidError::Inconsistent("pinned checker on an empty point")),
+            }
+            pieces.set_pinned(i, true);
+        }
+        pieces.set(Pieces::START, Player::White, self.waiting[0]);
+        pieces.set(Pieces::BLACK_START, Player::Black, self.waiting[1]);
+        white += self.waiting[0];
+        black += self.waiting[1];
+        let checkers = self.variant.checkers();
+        for (player, count) in [(Player::White, white), (Player::Black, black)] {
+            if count > checkers {
+                return Err(XgidError::TooManyCheckers { player, count });
+            }
+        }
+        pieces.set(Pieces::GOAL, Player::White, checkers - white);
+        pieces.set(Pieces::BLACK_GOAL, Player::Black, checkers - black);
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum XgidError {
+    MissingPrefix,
+    FieldCount(usize),
+    PositionLength(usize),
+    BadChecker { pos: usize, found: char },
+    TooManyCheckers { player: Player, count: usize },
+    BadNumber { field: usize, value: String },
+    BadCubePosition(String),
+    BadPlayer(String),
+    InvalidDice(String),
+    Inconsistent(&'static str),
+    BadVariant(String),
+}
+impl std::fmt::Display for XgidError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XgidError::MissingPrefix => write!(f, "missing '=' after XGID"),
+            XgidError::FieldCount(n) => write!(f, "expected 10 or 11 fields, found {}", n),
+            XgidError::PositionLength(n) => {
+                write!(
+                    f,
+                    "expected {} position chars, found {}",
+                    Pieces::BOARD_SIZE + 2,
+                    n
+                )
+            }
+            XgidError::BadChecker { pos, found } => {
+                write!(f, "bad checker '{}' at position {}", found, pos)
+            }
+            XgidError::TooManyCheckers { player, count } => {
+                write!(f, "{:?} has {} checkers on the board", player, count)
+            }
+            XgidError::BadNumber { field, value } => {
+                write!(f, "bad number '{}' in field {}", value, field)
+            }
+            XgidError::BadCubePosition(s) => write!(f, "bad cube position '{}'", s),
+            XgidError::BadPlayer(s) => write!(f, "bad player '{}'", s),
+            XgidError::InvalidDice(s) => write!(f, "invalid dice '{}'", s),
+            XgidError::Inconsistent(s) => write!(f, "inconsistent position: {}", s),
+            XgidError::BadVariant(s) => write!(f, "bad variant '{}'", s),
         }
     }
 }
+impl std::error::Error for XgidError {}
+impl Default for Board {
+    fn default() -> Self {
+        Board::new()
+    }
+}
 impl std::fmt::Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?} ", self.state())?;
@@ -782,9 +1330,9 @@ impl std::fmt::Display for Board {
         if self.to_roll {
             write!(f, "To roll... ")?;
         }
-        write!(f, "\n")?;
+        writeln!(f)?;
         write!(f, "Score: {:?} ", self.game.score)?;
-        write!(f, "Length: {:?} \n", self.game.length)?;
+        writeln!(f, "Length: {:?} ", self.game.length)?;
         use std::fmt::Write;
         let mut board = String::new();
         fn print_piece(
@@ -817,11 +1365,11 @@ impl std::fmt::Display for Board {
             Ok(())
         }
         if self.player == Some(Player::Black) {
-            write!(board, " 12 11 10  9  8  7   6  5  4  3  2  1\n")?;
+            writeln!(board, " 12 11 10  9  8  7   6  5  4  3  2  1")?;
         } else {
-            write!(board, " 13 14 15 16 17 18  19 20 21 22 23 24\n")?;
+            writeln!(board, " 13 14 15 16 17 18  19 20 21 22 23 24")?;
         }
-        write!(board, "+-=--*--=--*--=--*-+-=--*--=--*--=--*-+\n")?;
+        writeln!(board, "+-=--*--=--*--=--*-+-=--*--=--*--=--*-+")?;
         for c in 0..6 {
             write!(board, "|")?;
             for i in 13..25 {
@@ -829,9 +1377,9 @@ impl std::fmt::Display for Board {
             }
             write!(board, "|")?;
             print_piece(&mut board, &self.pieces, c, Pieces::BLACK_GOAL)?;
-            write!(board, "\n")?;
+            writeln!(board)?;
         }
-        write!(board, "+------------------+------------------+\n")?;
+        writeln!(board, "+------------------+------------------+")?;
         for c in (0..6).rev() {
             write!(board, "|")?;
             for i in (1..13).rev() {
@@ -839,13 +1387,13 @@ impl std::fmt::Display for Board {
             }
             write!(board, "|")?;
             print_piece(&mut board, &self.pieces, c, Pieces::GOAL)?;
-            write!(board, "\n")?;
+            writeln!(board)?;
         }
-        write!(board, "+-*--=--*--=--*--=-+-*--=--*--=--*--=-+\n")?;
+        writeln!(board, "+-*--=--*--=--*--=-+-*--=--*--=--*--=-+")?;
         if self.player == Some(Player::Black) {
-            write!(board, " 13 14 15 16 17 18  19 20 21 22 23 24\n")?;
+            writeln!(board, " 13 14 15 16 17 18  19 20 21 22 23 24")?;
         } else {
-            write!(board, " 12 11 10  9  8  7   6  5  4  3  2  1\n")?;
+            writeln!(board, " 12 11 10  9  8  7   6  5  4  3  2  1")?;
         }
         if let Some((_, c)) = self.pieces.get(Pieces::BAR) {
             if c > 0 {
@@ -857,6 +1405,14 @@ impl std::fmt::Display for Board {
                 write!(board, "BAR B: {}  ", c)?;
             }
         }
+        for i in (1..Pieces::BAR).filter(|&i| self.pieces.pinned(i)) {
+            write!(board, "PINNED: {}  ", i)?;
+        }
+        for (name, i) in [("W", Pieces::START), ("B", Pieces::BLACK_START)] {
+            if let Some((_, c)) = self.pieces.get(i) {
+                write!(board, "START {}: {}  ", name, c)?;
+            }
+        }
         write!(f, "{}", board)
     }
 }
@@ -888,6 +1444,32 @@ mod test {
         }
     }
 
+    #[test]
+    fn print_acey_deucey_moves() {
+        let mut b = Board::with_variant(Variant::AceyDeucey);
+        b.init_roll(Dice(4, 3));
+        println!("{}", b);
+        let moves = b.moves();
+        // every checker is still to enter: two of them, or one that enters and runs on
+        assert_eq!(moves.len(), 2);
+        for m in moves {
+            println!("{}", m.to_str());
+            assert!(m.0.iter().any(|&(from, _, _)| from == Pieces::BAR));
+        }
+
+        let mut b = Board::with_variant(Variant::AceyDeucey);
+        b.init_roll(Dice(4, 3));
+        b.act(&Action::Move(Move(vec![(25, 21, false), (25, 22, false)])));
+        // Black enters all four sixes past White's two checkers
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(6, 6)));
+        let moves = b.moves();
+        assert!(moves.contains(&Move(vec![(25, 19, false); 4])));
+        for m in moves {
+            println!("{}", m.to_str());
+        }
+    }
+
     #[test]
     fn single_game() {
         let mut b = Board::new();
@@ -896,7 +1478,7 @@ mod test {
 
         let act = &b.actions()[0];
         println!("{:?}", act);
-        b.act(&act);
+        b.act(act);
         println!("{}", b);
 
         assert_eq!(b.state(), State::ToDouble);
@@ -921,12 +1503,32 @@ mod test {
         assert_eq!(b.pieces.get(Pieces::BAR), Some((Player::White, 1)));
     }
 
+    #[test]
+    fn roll_actions() {
+        // the opening roll can't be a double, and decides who plays it
+        let b = Board::new();
+        let acts = b.actions();
+        assert_eq!(acts.len(), 30);
+        assert!(acts.contains(&Action::InitRoll(Dice(1, 2))));
+        assert!(!acts.contains(&Action::InitRoll(Dice(3, 3))));
+
+        // later rolls are Roll, one per unordered pair of dice
+        let mut b = Board::new();
+        b.init_roll(Dice(2, 1));
+        b.act(&b.actions()[0].clone());
+        b.act(&Action::NoDouble);
+        let acts = b.actions();
+        assert_eq!(acts.len(), 21);
+        assert!(acts.iter().all(|a| matches!(a, Action::Roll(_))));
+        assert!(acts.contains(&Action::Roll(Dice(3, 3))));
+    }
+
     #[test]
     fn big_roll() {
         let mut b = Board::new();
         b.init_roll(Dice(5, 6));
         let act = &b.actions()[0];
-        b.act(&act);
+        b.act(act);
         let mut i = 0;
         while b.state() != State::MatchEnd {
             b.act(&Action::NoDouble);
@@ -934,10 +1536,10 @@ mod test {
             println!("{}", b);
             let act = &b.actions()[0];
             println!("{:?}", act);
-            b.act(&act);
+            b.act(act);
             i += 1;
             if i > 39 {
-                assert!(false, "infinite loop")
+                panic!("infinite loop")
             }
         }
         print!("{}", b);
@@ -956,7 +1558,7 @@ mod test {
         b.game.length = 3;
         b.init_roll(Dice(5, 6));
         let act = &b.actions()[0];
-        b.act(&act);
+        b.act(act);
 
         assert_eq!(b.player, Some(Player::White));
         b.act(&Action::Double);
@@ -979,7 +1581,7 @@ mod test {
         b.game.length = 3;
         b.init_roll(Dice(5, 6));
         let act = &b.actions()[0];
-        b.act(&act);
+        b.act(act);
 
         assert_eq!(b.player, Some(Player::White));
         b.act(&Action::Double);
@@ -996,11 +1598,130 @@ mod test {
                 position: Some(Player::Black),
                 level: 1,
                 max_level: Cube::DEFAULT_MAX_LEVEL,
-                doubled: false
+                doubled: false,
+                beavers: 0,
             }
         )
     }
 
+    #[test]
+    fn beaver_raccoon() {
+        let mut b = Board::new();
+        b.game = Match::money(false, true);
+        b.init_roll(Dice(5, 6));
+        let act = &b.actions()[0];
+        b.act(act);
+        b.act(&Action::Double);
+        assert_eq!(
+            b.actions(),
+            vec![Action::Pass, Action::Take, Action::Beaver]
+        );
+
+        let mut beaver = b.clone();
+        beaver.act(&Action::Beaver);
+        assert_eq!(beaver.state(), State::Doubled);
+        assert_eq!(beaver.player, Some(Player::White));
+        assert_eq!(beaver.on_roll(), Player::White);
+        assert_eq!(beaver.actions(), vec![Action::Take, Action::Raccoon]);
+        let mut take = beaver.clone();
+        take.act(&Action::Take);
+        assert_eq!(take.state(), State::ToRoll);
+        assert_eq!(take.player, Some(Player::White));
+        assert_eq!(take.cube.value(), 4);
+        assert_eq!(take.cube.owner(), Some(Player::Black));
+
+        // the beaver is kept through the XGID
+        let id = beaver.xgid();
+        assert!(id.ends_with(":1:-1:1:B:0:0:2:0:10"), "{}", id);
+        assert_eq!(Board::from_xgid(&id).unwrap(), beaver);
+
+        beaver.act(&Action::Raccoon);
+        assert_eq!(beaver.player, Some(Player::Black));
+        assert_eq!(Board::from_xgid(&beaver.xgid()).unwrap(), beaver);
+        assert_eq!(beaver.actions(), vec![Action::Take]);
+        beaver.act(&Action::Take);
+        assert_eq!(beaver.player, Some(Player::White));
+        assert_eq!(beaver.cube.value(), 8);
+        assert_eq!(beaver.cube.owner(), Some(Player::White));
+
+        // only for money, and not past the maximum cube
+        b.game = Match::with_length(7);
+        assert_eq!(b.actions(), vec![Action::Pass, Action::Take]);
+        b.game = Match::money(false, true);
+        b.cube.max_level = 1;
+        assert_eq!(b.actions(), vec![Action::Pass, Action::Take]);
+    }
+
+    #[test]
+    fn jacoby() {
+        // White bears off the last checker with all of Black's checkers at home
+        let xgid = "XGID=-A-----------------o------:0:0:1:21:0:0:0:0:10";
+        for (jacoby, level, owner, points) in [
+            (false, 0, None, 2),
+            (true, 0, None, 1),
+            (true, 1, Some(Player::White), 4),
+        ] {
+            let mut b = Board::from_xgid(xgid).unwrap();
+            b.game = Match::money(jacoby, false);
+            b.cube.level = level;
+            b.cube.position = owner;
+            let act = &b.actions()[0];
+            b.act(act);
+            assert_eq!(
+                b.result,
+                Some(Result {
+                    player: Player::White,
+                    score: points
+                })
+            );
+            assert_eq!(b.game.score, (points, 0));
+        }
+    }
+
+    #[test]
+    fn variants() {
+        let standard = Board::new();
+        assert_eq!(standard.pieces.pips(Player::White), 167);
+        for (variant, pips) in [
+            (Variant::Nackgammon, 194),
+            (Variant::Hypergammon(1), 24),
+            (Variant::Hypergammon(3), 69),
+        ] {
+            let b = Board::with_variant(variant);
+            for p in [Player::White, Player::Black] {
+                assert_eq!(b.pieces.pips(p), pips);
+                assert_eq!(b.pieces.borne_off(p), 0);
+            }
+            let read = Board::from_xgid(&b.xgid()).unwrap();
+            assert_eq!((read.pieces, read.variant), (b.pieces, variant));
+            // the moves and transcripts of random play read back
+            notation::test::parse_every_move(variant, 1);
+            mat::test::random_matches(variant, 10);
+        }
+        assert_eq!(standard.xgid().split(':').count(), 10);
+
+        // the last checker borne off with the other still in White's home: a gammon
+        let xgid = "XGID=-A----------------------a-:0:0:1:21:0:0:0:0:10:H1";
+        let mut b = Board::from_xgid(xgid).unwrap();
+        assert_eq!(b.pieces.borne_off(Player::Black), 0);
+        let act = &b.actions()[0];
+        b.act(act);
+        assert_eq!(b.result.unwrap().score, 2);
+        b.act(&Action::Reset);
+        assert_eq!(b.variant, Variant::Hypergammon(1));
+        assert_eq!(b.pieces, Variant::Hypergammon(1).pieces());
+
+        let two = xgid.replace("-A-", "-B-");
+        assert!(matches!(
+            Board::from_xgid(&two),
+            Err(XgidError::TooManyCheckers { count: 2, .. })
+        ));
+        assert!(matches!(
+            Board::from_xgid(&xgid.replace("H1", "H4")),
+            Err(XgidError::BadVariant(_))
+        ));
+    }
+
     #[test]
     fn move_ord() {
         assert!(
@@ -1023,17 +1744,81 @@ mod test {
         );
 
         let id = "XGID=-b----E-C---eE---c-e----B---:1:1:1:11:1:2:1:3:10";
-        let b = Board::from_xgid(id);
+        let b = Board::from_xgid(id).unwrap();
         assert_eq!(b.xgid(), id);
     }
     #[test]
+    fn xgid_errors() {
+        let ok = "-b----E-C---eE---c-e----B---";
+        let err = |id: &str| Board::from_xgid(id).unwrap_err();
+        assert_eq!(err("-b----E-C"), XgidError::MissingPrefix);
+        assert_eq!(
+            err(&format!("XGID={}:0:0:1:11:0:0:0", ok)),
+            XgidError::FieldCount(8)
+        );
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e----B---A:0:0:1:11:0:0:0:1:10"),
+            XgidError::PositionLength(29)
+        );
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e--z-B---:0:0:1:11:0:0:0:1:10"),
+            XgidError::BadChecker {
+                pos: 22,
+                found: 'z'
+            }
+        );
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e----B--A:0:0:1:11:0:0:0:1:10"),
+            XgidError::BadChecker {
+                pos: 27,
+                found: 'A'
+            }
+        );
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e----C---:0:0:1:11:0:0:0:1:10"),
+            XgidError::TooManyCheckers {
+                player: Player::White,
+                count: 16
+            }
+        );
+        assert_eq!(
+            err(&format!("XGID={}:x:0:1:11:0:0:0:1:10", ok)),
+            XgidError::BadNumber {
+                field: 1,
+                value: "x".to_owned()
+            }
+        );
+        assert_eq!(
+            err(&format!("XGID={}:0:2:1:11:0:0:0:1:10", ok)),
+            XgidError::BadCubePosition("2".to_owned())
+        );
+        assert_eq!(
+            err(&format!("XGID={}:0:0:w:11:0:0:0:1:10", ok)),
+            XgidError::BadPlayer("w".to_owned())
+        );
+        assert_eq!(
+            err(&format!("XGID={}:0:0:1:17:0:0:0:1:10", ok)),
+            XgidError::InvalidDice("17".to_owned())
+        );
+        assert_eq!(
+            err(&format!("XGID={}:0:0::11:0:0:0:1:10", ok)),
+            XgidError::Inconsistent("dice or double without a player")
+        );
+        assert_eq!(
+            err(&format!("XGID={}:0:0:1:11:0:4:0:3:10", ok)),
+            XgidError::Inconsistent("score above the match length")
+        );
+        let b: Board = format!("XGID={}:0:0:1:11:0:0:0:1:10", ok).parse().unwrap();
+        assert_eq!(b.state(), State::ToMove);
+    }
+    #[test]
     fn moves() {
-        let b = Board::from_xgid("XGID=-A----------------------a-:0:0:1:11:0:0:0:1:10");
+        let b = Board::from_xgid("XGID=-A----------------------a-:0:0:1:11:0:0:0:1:10").unwrap();
         let moves = b.moves();
         println!("{:?}", moves);
         assert_eq!(moves, vec![Move(vec![(1, 0, false)])]);
 
-        let b = Board::from_xgid("XGID=-a------------------A-----:0:0:1:11:0:0:0:1:10");
+        let b = Board::from_xgid("XGID=-a------------------A-----:0:0:1:11:0:0:0:1:10").unwrap();
         print!("{}", b);
         let moves = b.moves();
         println!("{:?}", moves);
@@ -1047,27 +1832,57 @@ mod test {
             ])]
         );
     }
+    #[test]
+    fn dance() {
+        let b = Board::from_xgid("XGID=------E------------bbbbbbA--:0:0:1:31:0:0:0:1:10").unwrap();
+        assert_eq!(b.moves(), vec![Move::DANCE]);
+        assert_eq!(b.actions(), vec![Action::Move(Move::DANCE)]);
+    }
+    #[test]
+    fn enter_from_bar() {
+        let b = Board::from_xgid("XGID=------E------------bbb-bbA--:0:0:1:31:0:0:0:1:10").unwrap();
+        assert_eq!(b.moves(), vec![Move(vec![(25, 22, false), (6, 5, false)])]);
+    }
+    #[test]
+    fn accessors() {
+        let b = Board::from_xg_id("XGID=a-B-BBBB----------------bA:0:0:-1:41:2:3:1:5:10").unwrap();
+        let ps = &b.pieces;
+        assert_eq!(ps.point(Player::White, 2), 2);
+        assert_eq!(ps.point(Player::Black, 1), 2);
+        assert_eq!(ps.point(Player::Black, 23), 0);
+        assert_eq!(ps.bar(Player::White), 1);
+        assert_eq!(ps.bar(Player::Black), 1);
+        assert_eq!(ps.borne_off(Player::White), 4);
+        assert_eq!(ps.borne_off(Player::Black), 12);
+        assert_eq!(b.dice.dice(), Some(Dice(4, 1)));
+        assert_eq!(b.cube.owner(), None);
+        assert_eq!(b.cube.value(), 1);
+        assert_eq!(b.score(), (2, 3));
+    }
+
     #[test]
     fn reverse() {
         let ps = Pieces(
             vec![
                 1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 5, -6,
-                -7,
+                -7, 8, -9,
             ]
             .into_iter()
             .map(Piece)
             .collect(),
+            1 << 2,
         );
         let r = ps.reverse();
         assert_eq!(
             r.0,
             vec![
                 -6, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 2, -7, 1,
-                5
+                5, -9, 8,
             ]
             .into_iter()
             .map(Piece)
             .collect::<Vec<_>>()
-        )
+        );
+        assert!(r.pinned(23) && !r.pinned(2));
     }
 }
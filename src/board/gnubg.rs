@@ -0,0 +1,452 @@
+// GNU Backgammon Position ID and Match ID
+// https://www.gnu.org/software/gnubg/manual/html_node/A-technical-description-of-the-Position-ID.html
+// https://www.gnu.org/software/gnubg/manual/html_node/A-technical-description-of-the-Match-ID.html
+// gnubg player 0 is Black and player 1 is White.
+use super::{Board, Cube, Dice, DiceRoll, Match, Pieces, Player, Result, State, Variant};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const POSITION_ID_LEN: usize = 14;
+const MATCH_ID_LEN: usize = 12;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GnubgIdError {
+    PositionIdLength(usize),
+    MatchIdLength(usize),
+    BadChar { pos: usize, found: char },
+    BadPosition(&'static str),
+    TooManyCheckers { player: Player, count: usize },
+    BadMatchId(&'static str),
+    // a board the ids cannot describe
+    Unsupported(&'static str),
+}
+impl std::fmt::Display for GnubgIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GnubgIdError::PositionIdLength(n) => {
+                write!(
+                    f,
+                    "position id must be {} chars, found {}",
+                    POSITION_ID_LEN, n
+                )
+            }
+            GnubgIdError::MatchIdLength(n) => {
+                write!(f, "match id must be {} chars, found {}", MATCH_ID_LEN, n)
+            }
+            GnubgIdError::BadChar { pos, found } => {
+                write!(f, "bad base64 char '{}' at position {}", found, pos)
+            }
+            GnubgIdError::BadPosition(s) => write!(f, "bad position id: {}", s),
+            GnubgIdError::TooManyCheckers { player, count } => {
+                write!(f, "{:?} has {} checkers on the board", player, count)
+            }
+            GnubgIdError::BadMatchId(s) => write!(f, "bad match id: {}", s),
+            GnubgIdError::Unsupported(s) => write!(f, "not in gnubg ids: {}", s),
+        }
+    }
+}
+impl std::error::Error for GnubgIdError {}
+
+fn encode(bytes: &[u8], len: usize) -> String {
+    let mut s = String::new();
+    for chunk in bytes.chunks(3) {
+        let mut b = [0u8; 3];
+        b[..chunk.len()].copy_from_slice(chunk);
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            s.push(BASE64[(n >> (18 - 6 * i)) & 0x3f] as char);
+        }
+    }
+    s.truncate(len);
+    s
+}
+fn decode(s: &str, bytes: usize) -> std::result::Result<Vec<u8>, GnubgIdError> {
+    let mut bits = vec![];
+    for (pos, c) in s.chars().enumerate() {
+        let v = BASE64
+            .iter()
+            .position(|&b| b as char == c)
+            .ok_or(GnubgIdError::BadChar { pos, found: c })?;
+        for i in (0..6).rev() {
+            bits.push((v >> i) & 1 == 1);
+        }
+    }
+    Ok((0..bytes)
+        .map(|i| (0..8).fold(0u8, |b, j| b | if bits[i * 8 + j] { 0x80 >> j } else { 0 }))
+        .collect())
+}
+
+struct Bits(Vec<u8>);
+impl Bits {
+    fn get(&self, i: usize) -> bool {
+        self.0[i / 8] & (1 << (i % 8)) != 0
+    }
+    fn set(&mut self, i: usize) {
+        self.0[i / 8] |= 1 << (i % 8);
+    }
+    fn read(&self, from: usize, len: usize) -> usize {
+        (0..len).fold(0, |v, i| v | (self.get(from + i) as usize) << i)
+    }
+    fn write(&mut self, from: usize, len: usize, v: usize) {
+        for i in 0..len {
+            if (v >> i) & 1 == 1 {
+                self.set(from + i);
+            }
+        }
+    }
+}
+
+fn gnubg_player(p: Player) -> usize {
+    match p {
+        Player::Black => 0,
+        Player::White => 1,
+    }
+}
+fn from_gnubg_player(p: usize) -> Player {
+    if p == 0 {
+        Player::Black
+    } else {
+        Player::White
+    }
+}
+
+const NO_GAME: usize = 0;
+const PLAYING: usize = 1;
+const GAME_OVER: usize = 2;
+const DROPPED: usize = 4;
+
+impl Board {
+    // the player who rolls next or is rolling; the position id is written from this side
+    pub fn on_roll(&self) -> Player {
+        match self.state() {
+            // the taker of a beaver is the doubler
+            State::Doubled if self.cube.beavers == 1 => self.player.unwrap(),
+            State::Doubled => self.player.unwrap().opponent(),
+            State::End | State::MatchEnd => self.result.map_or(Player::White, |r| r.player),
+            _ => self.player.unwrap_or(Player::White),
+        }
+    }
+
+    // the ids have no variants or beavers
+    fn gnubg_supported(&self) -> std::result::Result<(), GnubgIdError> {
+        if self.variant != Variant::Standard {
+            return Err(GnubgIdError::Unsupported("variants"));
+        }
+        if self.cube.beavers > 0 {
+            return Err(GnubgIdError::Unsupported("beavers"));
+        }
+        Ok(())
+    }
+
+    pub fn position_id(&self) -> std::result::Result<String, GnubgIdError> {
+        self.gnubg_supported()?;
+        let on_roll = self.on_roll();
+        let mut key = Bits(vec![0; 10]);
+        let mut i = 0;
+        for p in [on_roll.opponent(), on_roll] {
+            let ps = self.pieces.reversed(p);
+            for point in 1..=Pieces::BAR {
+                if let Some((o, c)) = ps.get(point) {
+                    if o == p {
+                        key.write(i, c, (1 << c) - 1);
+                        i += c;
+                    }
+                }
+                i += 1;
+            }
+        }
+        Ok(encode(&key.0, POSITION_ID_LEN))
+    }
+
+    pub fn match_id(&self) -> std::result::Result<String, GnubgIdError> {
+        self.gnubg_supported()?;
+        let state = self.state();
+        let on_roll = self.on_roll();
+        let doubled = self.cube.doubled;
+        let mut key = Bits(vec![0; 9]);
+        key.write(0, 4, self.cube.level);
+        let owner = if doubled {
+            // the doubler owned the cube unless it was centered
+            if self.cube.level == 0 {
+                None
+            } else {
+                Some(on_roll)
+            }
+        } else {
+            self.cube.position
+        };
+        key.write(4, 2, owner.map_or(3, gnubg_player));
+        key.write(6, 1, gnubg_player(on_roll));
+        key.write(7, 1, self.game.crawford as usize);
+        let game_state = match state {
+            State::Init => NO_GAME,
+            State::End | State::MatchEnd if doubled => DROPPED,
+            State::End | State::MatchEnd => GAME_OVER,
+            _ => PLAYING,
+        };
+        key.write(8, 3, game_state);
+        let turn = if state == State::Doubled {
+            on_roll.opponent()
+        } else {
+            on_roll
+        };
+        key.write(11, 1, gnubg_player(turn));
+        key.write(12, 1, doubled as usize);
+        if let Some(Dice(a, b)) = self.dice.0 {
+            key.write(15, 3, a);
+            key.write(18, 3, b);
+        }
+        key.write(21, 15, self.game.length);
+        key.write(36, 15, self.game.score.1);
+        key.write(51, 15, self.game.score.0);
+        Ok(encode(&key.0, MATCH_ID_LEN))
+    }
+
+    pub fn from_gnubg_id(
+        position_id: &str,
+        match_id: &str,
+    ) -> std::result::Result<Board, GnubgIdError> {
+        let len = position_id.chars().count();
+        if len != POSITION_ID_LEN {
+            return Err(GnubgIdError::PositionIdLength(len));
+        }
+        let len = match_id.chars().count();
+        if len != MATCH_ID_LEN {
+            return Err(GnubgIdError::MatchIdLength(len));
+        }
+        let pos = Bits(decode(position_id, 10)?);
+        let key = Bits(decode(match_id, 9)?);
+
+        let on_roll = from_gnubg_player(key.read(6, 1));
+        let turn = from_gnubg_player(key.read(11, 1));
+        let game_state = key.read(8, 3);
+        let doubled = key.read(12, 1) == 1;
+
+        let mut pieces = Pieces::empty();
+        let mut i = 0;
+        for p in [on_roll.opponent(), on_roll] {
+            let mut ps = Pieces::empty();
+            let mut total = 0;
+            for point in 1..=Pieces::BAR {
+                let mut c = 0;
+                while i < 80 && pos.get(i) {
+                    c += 1;
+                    i += 1;
+                }
+                if i >= 80 {
+                    return Err(GnubgIdError::BadPosition("too few points"));
+                }
+                i += 1;
+                total += c;
+                ps.set(point, p, c);
+            }
+            if total > Pieces::MAX_PIECES {
+                return Err(GnubgIdError::TooManyCheckers {
+                    player: p,
+                    count: total,
+                });
+            }
+            ps.set(Pieces::GOAL, p, Pieces::MAX_PIECES - total);
+            // copy this player's checkers into the white oriented board
+            let ps = ps.reversed(p);
+            for j in 0..ps.0.len() {
+                if let Some((_, c)) = ps.get(j) {
+                    if pieces.get(j).is_some() {
+                        return Err(GnubgIdError::BadPosition("both players on a point"));
+                    }
+                    pieces.set(j, p, c);
+                }
+            }
+        }
+
+        let level = key.read(0, 4);
+        let position = match key.read(4, 2) {
+            0 => Some(Player::Black),
+            1 => Some(Player::White),
+            3 => None,
+            _ => return Err(GnubgIdError::BadMatchId("bad cube owner")),
+        };
+        let cube = Cube {
+            position: if doubled {
+                Some(on_roll.opponent())
+            } else {
+                position
+            },
+            level,
+            doubled,
+            max_level: Cube::DEFAULT_MAX_LEVEL,
+            beavers: 0,
+        };
+        if cube.level > cube.max_level {
+            return Err(GnubgIdError::BadMatchId("cube level above the maximum"));
+        }
+
+        let (a, b) = (key.read(15, 3), key.read(18, 3));
+        let dice = match (a, b) {
+            (0, 0) => DiceRoll::new(),
+            (1..=6, 1..=6) => DiceRoll::roll(a, b),
+            _ => return Err(GnubgIdError::BadMatchId("bad dice")),
+        };
+        let score = (key.read(51, 15), key.read(36, 15));
+        let length = key.read(21, 15);
+        // a length of 0 is money play; the Jacoby rule and beavers are not in the id
+        let game = if length == 0 {
+            Match {
+                score,
+                ..Match::money(false, false)
+            }
+        } else {
+            Match {
+                score,
+                crawford: key.read(7, 1) == 1,
+                ..Match::with_length(length)
+            }
+        };
+        if !game.is_money() && (game.score.0 > game.length || game.score.1 > game.length) {
+            return Err(GnubgIdError::BadMatchId("score above the match length"));
+        }
+
+        let mut board = Board {
+            pieces,
+            dice,
+            cube,
+            to_roll: false,
+            player: None,
+            game,
+            result: None,
+            variant: Variant::Standard,
+            bonus: false,
+        };
+        match game_state {
+            NO_GAME => {}
+            PLAYING => {
+                board.player = Some(if doubled { turn } else { on_roll });
+                board.to_roll = !doubled
+                    && dice.0.is_none()
+                    && cube.position == board.player.map(|p| p.opponent());
+            }
+            GAME_OVER | DROPPED => {
+                let score = if game_state == DROPPED {
+                    cube.value()
+                } else {
+                    board.pieces.goal(on_roll) * cube.value()
+                };
+                if score == 0 {
+                    return Err(GnubgIdError::BadMatchId("game over without a winner"));
+                }
+                board.result = Some(Result {
+                    player: on_roll,
+                    score,
+                });
+            }
+            _ => return Err(GnubgIdError::BadMatchId("unsupported game state")),
+        }
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::Action;
+
+    #[test]
+    fn start_position() {
+        let b = Board::new();
+        assert_eq!(b.position_id().unwrap(), "4HPwATDgc/ABMA");
+        let b = Board::from_gnubg_id("4HPwATDgc/ABMA", &b.match_id().unwrap()).unwrap();
+        assert_eq!(b, Board::new());
+    }
+
+    #[test]
+    fn manual_match_id() {
+        // cube 2 owned by player 0, player 1 to play 52, 9 point match, score 2-4
+        let b = Board::from_gnubg_id("4HPwATDgc/ABMA", "QYkqASAAIAAA").unwrap();
+        assert_eq!(b.state(), State::ToMove);
+        assert_eq!(b.player, Some(Player::White));
+        assert_eq!(b.dice, DiceRoll::roll(5, 2));
+        assert_eq!(b.cube.level, 1);
+        assert_eq!(b.cube.position, Some(Player::Black));
+        assert_eq!(b.game.length, 9);
+        assert_eq!(b.game.score, (4, 2));
+        assert_eq!(b.match_id().unwrap(), "QYkqASAAIAAA");
+    }
+
+    #[test]
+    fn round_trip() {
+        let ids = [
+            "XGID=-b----E-C---eE---c-e----B---:1:1:1:11:1:2:1:3:10",
+            "XGID=-A----------------------a-:0:0:1:11:0:0:0:1:10",
+            "XGID=-a------------------A-----:0:0:1:11:0:0:0:1:10",
+            "XGID=------E------------bbb-bbA--:0:0:1:31:0:0:0:1:10",
+            "XGID=-----A-----------------a--:0:0:1::0:0:0:3:10",
+            "XGID=-BD--ADA----b---baBbabc-A--b:3:-1:-1:66:0:0:0:5:10",
+        ];
+        for id in ids {
+            let b = Board::from_xgid(id).unwrap();
+            let g =
+                Board::from_gnubg_id(&b.position_id().unwrap(), &b.match_id().unwrap()).unwrap();
+            assert_eq!(g.xgid(), b.xgid(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn double_and_drop() {
+        let mut b = Board::new();
+        b.game.length = 3;
+        b.init_roll(Dice(5, 6));
+        b.act(&b.actions()[0].clone());
+        for act in [Action::Double, Action::Pass] {
+            b.act(&act);
+            let g =
+                Board::from_gnubg_id(&b.position_id().unwrap(), &b.match_id().unwrap()).unwrap();
+            assert_eq!(g, b);
+        }
+        assert_eq!(b.state(), State::End);
+    }
+
+    #[test]
+    fn money() {
+        let mut b =
+            Board::from_xgid("XGID=-b----E-C---eE---c-e----B-:0:0:1:52:3:1:0:0:10").unwrap();
+        assert!(b.game.is_money());
+        let g = Board::from_gnubg_id(&b.position_id().unwrap(), &b.match_id().unwrap()).unwrap();
+        assert_eq!(g, b);
+
+        b.act(&b.actions()[0].clone());
+        b.act(&Action::Double);
+        let g = Board::from_gnubg_id(&b.position_id().unwrap(), &b.match_id().unwrap()).unwrap();
+        assert_eq!(g, b);
+        assert_eq!(g.actions(), vec![Action::Pass, Action::Take]);
+
+        // the ids have no beavers
+        b.game.beaver = true;
+        b.act(&Action::Beaver);
+        assert_eq!(b.match_id(), Err(GnubgIdError::Unsupported("beavers")));
+    }
+
+    #[test]
+    fn variants() {
+        let b = Board::with_variant(Variant::Hypergammon(3));
+        assert_eq!(b.position_id(), Err(GnubgIdError::Unsupported("variants")));
+        assert_eq!(b.match_id(), Err(GnubgIdError::Unsupported("variants")));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Board::from_gnubg_id("4HPwATDgc/ABM", "QYkqASAAIAAA"),
+            Err(GnubgIdError::PositionIdLength(13))
+        );
+        assert_eq!(
+            Board::from_gnubg_id("4HPwATDgc/AB!A", "QYkqASAAIAAA"),
+            Err(GnubgIdError::BadChar {
+                pos: 12,
+                found: '!'
+            })
+        );
+        assert_eq!(
+            Board::from_gnubg_id("//////////////", "QYkqASAAIAAA"),
+            Err(GnubgIdError::BadPosition("too few points"))
+        );
+    }
+}
@@ -0,0 +1,550 @@
+// Jellyfish / GNU Backgammon .mat match transcripts
+// The left column is White and the right column is Black.
+use super::{Action, Board, Dice, Match, Move, Player, State, Variant};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MatError {
+    MissingLength,
+    BadLine(usize, String),
+    IllegalMove(usize, String),
+    UnexpectedAction(usize, String),
+    ScoreMismatch(usize, String),
+    Unsupported(usize, String),
+}
+impl std::fmt::Display for MatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatError::MissingLength => write!(f, "missing 'N point match' header"),
+            MatError::BadLine(l, s) => write!(f, "line {}: cannot read '{}'", l, s),
+            MatError::IllegalMove(l, s) => write!(f, "line {}: illegal move '{}'", l, s),
+            MatError::UnexpectedAction(l, s) => write!(f, "line {}: unexpected '{}'", l, s),
+            MatError::ScoreMismatch(l, s) => write!(f, "line {}: score mismatch '{}'", l, s),
+            MatError::Unsupported(l, s) => write!(f, "line {}: unsupported '{}'", l, s),
+        }
+    }
+}
+impl std::error::Error for MatError {}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Transcript {
+    pub length: usize,
+    // (White, Black)
+    pub names: (String, String),
+    pub actions: Vec<Action>,
+    // written as a '; [Variant "code"]' comment when not the standard game
+    pub variant: Variant,
+}
+
+// the right column starts after this many chars
+const RIGHT_COLUMN: usize = 20;
+const CELL_WIDTH: usize = 30;
+
+impl Transcript {
+    pub fn new(length: usize, white: &str, black: &str) -> Transcript {
+        Transcript {
+            length,
+            names: (white.to_owned(), black.to_owned()),
+            actions: vec![],
+            variant: Variant::Standard,
+        }
+    }
+
+    pub fn start(&self) -> Board {
+        let mut board = Board::with_variant(self.variant);
+        board.game = Match::with_length(self.length);
+        board
+    }
+    pub fn replay(&self) -> Board {
+        let mut board = self.start();
+        for act in &self.actions {
+            board.act(act);
+        }
+        board
+    }
+
+    pub fn parse(text: &str) -> std::result::Result<Transcript, MatError> {
+        let mut t = Transcript::new(0, "", "");
+        let mut board = Board::new();
+        // whether the header of the game marks it as the Crawford game
+        let mut crawford = false;
+        for (i, raw) in text.lines().enumerate() {
+            let n = i + 1;
+            let line = raw.trim();
+            let bad = || MatError::BadLine(n, line.to_owned());
+            if let Some(code) = line.strip_prefix("; [Variant \"") {
+                let code = code.strip_suffix("\"]").ok_or_else(bad)?;
+                t.variant = Variant::from_code(code).ok_or_else(bad)?;
+                continue;
+            }
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            if let Some(len) = line.strip_suffix("point match") {
+                t.length = len.trim().parse().map_err(|_| bad())?;
+                board = t.start();
+                continue;
+            }
+            if t.length == 0 {
+                return Err(MatError::MissingLength);
+            }
+            if line.starts_with("Game") {
+                match board.state() {
+                    State::Init => {}
+                    State::End => t.push(&mut board, Action::Reset),
+                    _ => return Err(MatError::UnexpectedAction(n, line.to_owned())),
+                }
+                crawford = line.contains("(Crawford)");
+                continue;
+            }
+            if line.contains(" : ") {
+                let (white, rest) = line.split_once(" : ").ok_or_else(bad)?;
+                crawford |= rest.contains("(Crawford)");
+                let rest = rest.replace("(Crawford)", "");
+                let rest = rest.trim();
+                let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(bad)?;
+                let (score0, rest) = rest.split_at(digits);
+                let (black, score1) = rest.split_once(" : ").ok_or_else(bad)?;
+                let score = (
+                    score0.parse::<usize>().map_err(|_| bad())?,
+                    score1.trim().parse::<usize>().map_err(|_| bad())?,
+                );
+                if score != board.game.score || crawford != board.game.crawford {
+                    return Err(MatError::ScoreMismatch(n, line.to_owned()));
+                }
+                if t.names.0.is_empty() {
+                    t.names = (white.trim().to_owned(), black.trim().to_owned());
+                }
+                continue;
+            }
+            if line.starts_with("Wins") {
+                // the winner's column, by the indentation write uses
+                let column = if raw.len() - raw.trim_start().len() > RIGHT_COLUMN {
+                    Player::Black
+                } else {
+                    Player::White
+                };
+                parse_wins(&board, n, column, line)?;
+                continue;
+            }
+            let (num, _) = line.split_once(')').ok_or_else(bad)?;
+            num.trim().parse::<usize>().map_err(|_| bad())?;
+            let start = raw.find(')').unwrap() + 1;
+            let offset = raw[start..].find(|c: char| !c.is_whitespace());
+            let mut column = match offset {
+                None => continue,
+                Some(o) if o >= RIGHT_COLUMN => Player::Black,
+                Some(_) => Player::White,
+            };
+            for cell in cells(&raw[start..]) {
+                t.parse_cell(&mut board, n, column, &cell)?;
+                column = column.opponent();
+            }
+        }
+        if t.length == 0 {
+            return Err(MatError::MissingLength);
+        }
+        Ok(t)
+    }
+
+    fn push(&mut self, board: &mut Board, act: Action) {
+        board.act(&act);
+        self.actions.push(act);
+    }
+
+    fn parse_cell(&mut self, board: &mut Board, n: usize, column: Player, cell: &str) -> MatResult {
+        let unexpected = || MatError::UnexpectedAction(n, cell.to_owned());
+        let state = board.state();
+        // after the opening roll, a cell is in the column of the player to act
+        let on_turn = state == State::Init || board.player == Some(column);
+        if cell.starts_with("Doubles") {
+            if state != State::ToDouble || !board.can_double() || !on_turn {
+                return Err(unexpected());
+            }
+            self.push(board, Action::Double);
+        } else if cell.starts_with("Takes") {
+            if state != State::Doubled || !on_turn {
+                return Err(unexpected());
+            }
+            self.push(board, Action::Take);
+        } else if cell.starts_with("Drops") || cell.starts_with("Passes") {
+            if state != State::Doubled || !on_turn {
+                return Err(unexpected());
+            }
+            self.push(board, Action::Pass);
+        } else if cell.starts_with("Wins") {
+            parse_wins(board, n, column, cell)?;
+        } else if let Some((roll, mov)) = cell.split_once(':') {
+            let d: Vec<usize> = roll
+                .chars()
+                .filter_map(|c| c.to_digit(10))
+                .map(|d| d as usize)
+                .collect();
+            if roll.len() != 2 || d.len() != 2 || d.iter().any(|&d| !(1..=6).contains(&d)) {
+                return Err(MatError::BadLine(n, cell.to_owned()));
+            }
+            let (hi, lo) = (d[0].max(d[1]), d[0].min(d[1]));
+            match state {
+                State::Init if hi == lo => return Err(unexpected()),
+                State::Init if column == Player::White => {
+                    self.push(board, Action::InitRoll(Dice(hi, lo)))
+                }
+                State::Init => self.push(board, Action::InitRoll(Dice(lo, hi))),
+                _ if !on_turn => return Err(unexpected()),
+                State::ToDouble => {
+                    self.push(board, Action::NoDouble);
+                    self.push(board, Action::Roll(Dice(d[0], d[1])));
+                }
+                State::ToRoll => self.push(board, Action::Roll(Dice(d[0], d[1]))),
+                State::ToChoose if hi == lo => self.push(board, Action::Choose(hi)),
+                _ => return Err(unexpected()),
+            }
+            let mov = Move::parse(board, mov)
+                .map_err(|e| MatError::IllegalMove(n, format!("{} ({})", cell, e)))?;
+            self.push(board, Action::Move(mov));
+        } else {
+            return Err(MatError::BadLine(n, cell.to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn write(&self) -> String {
+        let mut s = String::new();
+        if self.variant != Variant::Standard {
+            s.push_str(&format!("; [Variant \"{}\"]\n", self.variant.code()));
+        }
+        s.push_str(&format!(" {} point match\n", self.length));
+        let mut board = self.start();
+        let mut game = 0;
+        let mut cells: Vec<(Player, String)> = vec![];
+        let mut roll = None;
+        for act in &self.actions {
+            if board.state() == State::Init && roll.is_none() {
+                if game > 0 {
+                    s.push_str(&lines(&cells));
+                    cells.clear();
+                }
+                game += 1;
+                s.push_str(&self.game_header(&board, game));
+            }
+            let player = board.player;
+            board.act(act);
+            match act {
+                Action::InitRoll(Dice(a, b)) => roll = Some(Dice(*a.max(b), *a.min(b))),
+                Action::Roll(d) => roll = Some(*d),
+                // the double of choice is written as a roll of its own
+                Action::Choose(n) => roll = Some(Dice(*n, *n)),
+                Action::Move(m) => {
+                    let Dice(a, b) = roll.take().unwrap();
+                    let text = format!("{}{}: {}", a, b, m.to_str());
+                    cells.push((player.unwrap(), text.trim().to_owned()));
+                }
+                Action::Double => cells.push((
+                    player.unwrap(),
+                    format!("Doubles => {}", board.cube.value() * 2),
+                )),
+                // a transcript is of a match, where the cube cannot be beavered
+                Action::Beaver | Action::Raccoon => unreachable!(),
+                Action::Take => cells.push((player.unwrap(), "Takes".to_owned())),
+                Action::Pass => cells.push((player.unwrap(), "Drops".to_owned())),
+                Action::NoDouble | Action::Reset | Action::None => {}
+            }
+            if let (State::End | State::MatchEnd, Some(r)) = (board.state(), board.result) {
+                let plural = if r.score == 1 { "" } else { "s" };
+                let end = if board.state() == State::MatchEnd {
+                    " and the match"
+                } else {
+                    ""
+                };
+                cells.push((r.player, format!("Wins {} point{}{}", r.score, plural, end)));
+            }
+        }
+        s.push_str(&lines(&cells));
+        s
+    }
+
+    fn game_header(&self, board: &Board, game: usize) -> String {
+        let crawford = if board.game.crawford {
+            " (Crawford)"
+        } else {
+            ""
+        };
+        let white = format!(" {} : {}", self.names.0, board.game.score.0);
+        format!(
+            "\n Game {}{}\n{:<width$}{} : {}\n",
+            game,
+            crawford,
+            white,
+            self.names.1,
+            board.game.score.1,
+            width = CELL_WIDTH + 6
+        )
+    }
+}
+
+type MatResult = std::result::Result<(), MatError>;
+
+// "Wins n point(s)", checked against how the game on the board ended
+fn parse_wins(board: &Board, n: usize, column: Player, cell: &str) -> MatResult {
+    let Some(r) = board.result else {
+        return Err(MatError::Unsupported(n, cell.to_owned()));
+    };
+    let points = cell
+        .split_whitespace()
+        .nth(1)
+        .and_then(|p| p.parse::<usize>().ok())
+        .ok_or_else(|| MatError::BadLine(n, cell.to_owned()))?;
+    if r.player != column || r.score != points {
+        return Err(MatError::ScoreMismatch(n, cell.to_owned()));
+    }
+    Ok(())
+}
+
+// splits the text after "n)" into cells
+fn cells(s: &str) -> Vec<String> {
+    let mut cells: Vec<String> = vec![];
+    for token in s.split_whitespace() {
+        let starts = token.ends_with(':') && token.len() == 3
+            || ["Doubles", "Takes", "Drops", "Passes", "Wins"].contains(&token);
+        match cells.last_mut() {
+            Some(c) if !starts => {
+                c.push(' ');
+                c.push_str(token);
+            }
+            _ => cells.push(token.to_owned()),
+        }
+    }
+    cells
+}
+
+fn lines(cells: &[(Player, String)]) -> String {
+    let mut s = String::new();
+    let mut n = 0;
+    let mut left: Option<&str> = None;
+    for (p, c) in cells {
+        if c.starts_with("Wins") {
+            if let Some(l) = left.take() {
+                n += 1;
+                s.push_str(&format!("{:>3}) {}\n", n, l));
+            }
+            let pad = if *p == Player::White {
+                6
+            } else {
+                CELL_WIDTH + 6
+            };
+            s.push_str(&format!("{:pad$}{}\n", "", c, pad = pad));
+            continue;
+        }
+        if *p == Player::White {
+            if let Some(l) = left.replace(c) {
+                n += 1;
+                s.push_str(&format!("{:>3}) {}\n", n, l));
+            }
+        } else {
+            n += 1;
+            let l = left.take().unwrap_or("");
+            s.push_str(&format!(
+                "{:>3}) {:<width$}{}\n",
+                n,
+                l,
+                c,
+                width = CELL_WIDTH + 1
+            ));
+        }
+    }
+    if let Some(l) = left {
+        n += 1;
+        s.push_str(&format!("{:>3}) {}\n", n, l));
+    }
+    s
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::agent::RandomAgent;
+    use crate::dice::Rng;
+    use crate::play::play_match;
+
+    // writes and reads back seeded random 5-point matches
+    pub(crate) fn random_matches(variant: Variant, matches: u64) {
+        for seed in 0..matches {
+            let mut t = Transcript::new(5, "White", "Black");
+            t.variant = variant;
+            let mut b = t.start();
+            t.actions = play_match(
+                &mut b,
+                &mut RandomAgent::new(Rng::new(2 * seed)),
+                &mut RandomAgent::new(Rng::new(2 * seed + 1)),
+                &mut Rng::new(seed),
+            );
+            let s = t.write();
+            assert_eq!(Transcript::parse(&s), Ok(t), "seed {}", seed);
+        }
+    }
+
+    const MATCH: &str = "
+; [Event \"test\"]
+ 3 point match
+
+ Game 1
+ Alice : 0                            Bob : 0
+  1)                                  31: 8/5 6/5
+  2) 52: 13/8 13/11                   Doubles => 2
+  3)  Takes                           64: 24/18 13/9
+  4) 63: 13/7* 13/10                  43: bar/21 24/21
+  5)  Doubles => 4                    Drops
+      Wins 2 points
+
+ Game 2 (Crawford)
+ Alice : 2                            Bob : 0
+  1) 51: 13/8 6/5
+";
+
+    #[test]
+    fn parse() {
+        let t = Transcript::parse(MATCH).unwrap();
+        assert_eq!(t.length, 3);
+        assert_eq!(t.names, ("Alice".to_owned(), "Bob".to_owned()));
+        assert_eq!(t.actions[0], Action::InitRoll(Dice(1, 3)));
+        let b = t.replay();
+        assert_eq!(b.game.score, (2, 0));
+        assert!(b.game.crawford);
+        assert_eq!(b.player, Some(Player::Black));
+        assert_eq!(b.state(), State::ToDouble);
+    }
+
+    #[test]
+    fn write() {
+        let t = Transcript::parse(MATCH).unwrap();
+        let s = t.write();
+        assert!(s.contains("Game 2 (Crawford)"));
+        assert!(s.contains("Doubles => 4"));
+        assert!(s.contains("Wins 2 points"));
+        assert_eq!(Transcript::parse(&s).unwrap(), t);
+    }
+
+    #[test]
+    fn full_match() {
+        let mut t = Transcript::new(3, "White", "Black");
+        let mut b = t.start();
+        let mut i = 0;
+        while b.state() != State::MatchEnd {
+            let act = match b.state() {
+                State::Init => Action::InitRoll(Dice(4, 2)),
+                State::ToRoll => Action::Roll(DICE[i % DICE.len()]),
+                State::ToDouble if i % 7 == 3 && b.can_double() => Action::Double,
+                State::Doubled if i % 2 == 0 => Action::Pass,
+                _ => b.actions()[0].clone(),
+            };
+            i += 1;
+            t.push(&mut b, act);
+        }
+        let s = t.write();
+        assert!(s.contains("and the match"));
+        let p = Transcript::parse(&s).unwrap();
+        assert_eq!(p, t);
+    }
+
+    #[test]
+    fn random_play() {
+        random_matches(Variant::Standard, 40);
+    }
+
+    #[test]
+    fn acey_deucey() {
+        let mut t = Transcript::new(1, "White", "Black");
+        t.variant = Variant::AceyDeucey;
+        let mut b = t.start();
+        // White's 1-2s, each followed by a double of choice written as a roll
+        let mut i = 0;
+        while i < 20 || b.state() != State::ToDouble {
+            let act = match b.state() {
+                State::Init => Action::InitRoll(Dice(2, 1)),
+                State::ToRoll if i < 10 => Action::Roll(Dice(1, 2)),
+                State::ToRoll => Action::Roll(DICE[i % DICE.len()]),
+                State::ToChoose => Action::Choose(i % 6 + 1),
+                State::ToDouble => Action::NoDouble,
+                _ => b.actions()[0].clone(),
+            };
+            t.push(&mut b, act);
+            i += 1;
+        }
+        assert!(t.actions.iter().any(|a| matches!(a, Action::Choose(_))));
+        let s = t.write();
+        assert!(s.starts_with("; [Variant \"A\"]\n"));
+        let p = Transcript::parse(&s).unwrap();
+        assert_eq!(p, t);
+        assert_eq!(p.replay(), b);
+    }
+
+    const DICE: [Dice; 7] = [
+        Dice(3, 1),
+        Dice(6, 6),
+        Dice(5, 2),
+        Dice(4, 4),
+        Dice(6, 1),
+        Dice(2, 2),
+        Dice(5, 3),
+    ];
+
+    #[test]
+    fn errors() {
+        assert_eq!(Transcript::parse("Game 1"), Err(MatError::MissingLength));
+        let bad = MATCH.replace("24/21", "24/20");
+        assert_eq!(
+            Transcript::parse(&bad),
+            Err(MatError::IllegalMove(
+                10,
+                "43: bar/21 24/20 (moves 8 pips but the roll is 4-3)".to_owned()
+            ))
+        );
+        let bad = MATCH.replace("Wins 2 points", "Wins 4 points");
+        assert_eq!(
+            Transcript::parse(&bad),
+            Err(MatError::ScoreMismatch(12, "Wins 4 points".to_owned()))
+        );
+        let bad = MATCH.replace("      Wins", &format!("{:40}Wins", ""));
+        assert!(matches!(
+            Transcript::parse(&bad),
+            Err(MatError::ScoreMismatch(12, _))
+        ));
+        // a roll or a take in the column of the other player
+        let bad = MATCH.replace("  4) 63: 13/7* 13/10  ", &format!("  4) {:30}", ""));
+        assert_eq!(
+            Transcript::parse(&bad),
+            Err(MatError::UnexpectedAction(
+                10,
+                "43: bar/21 24/21".to_owned()
+            ))
+        );
+        let bad = MATCH.replace(
+            "  3)  Takes                           64: 24/18 13/9",
+            &format!("  3) {:31}Takes", ""),
+        );
+        assert_eq!(
+            Transcript::parse(&bad),
+            Err(MatError::UnexpectedAction(9, "Takes".to_owned()))
+        );
+        // the Crawford game as the match has it
+        let bad = MATCH.replace("Game 2 (Crawford)", "Game 2");
+        assert!(matches!(
+            Transcript::parse(&bad),
+            Err(MatError::ScoreMismatch(15, _))
+        ));
+        let bad = MATCH.replace("Game 1", "Game 1 (Crawford)");
+        assert!(matches!(
+            Transcript::parse(&bad),
+            Err(MatError::ScoreMismatch(6, _))
+        ));
+        let moved = MATCH
+            .replace("Game 2 (Crawford)", "Game 2")
+            .replace("Alice : 2", "Alice : 2 (Crawford)");
+        assert_eq!(Transcript::parse(&moved), Transcript::parse(MATCH));
+
+        let bad = MATCH.replace("  3)  Takes", "  3)  Drops");
+        assert!(matches!(
+            Transcript::parse(&bad),
+            Err(MatError::ScoreMismatch(..) | MatError::UnexpectedAction(..))
+        ));
+    }
+}
@@ -0,0 +1,325 @@
+// Standard move notation, e.g. "24/18* 13/11", "bar/22 24/18/13", "6/off(2)"
+// Points are numbered from the mover's side: bar is 25 and off is 0.
+// A * marks a hit, or in Plakoto a pin.
+use super::{Board, Dice, Move, Pieces, State};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MoveError {
+    NotToMove,
+    Syntax(String),
+    Backward { from: usize, to: usize },
+    NoChecker(usize),
+    BarFirst,
+    Blocked(usize),
+    NoHit(usize),
+    BearOff(usize),
+    TooFar { pips: usize, dice: Dice },
+    MustPlayMore,
+    NotLegal(String),
+}
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::NotToMove => write!(f, "no player is to move"),
+            MoveError::Syntax(s) => write!(f, "cannot read '{}'", s),
+            MoveError::Backward { from, to } => {
+                write!(f, "{}/{} moves backward", from, to)
+            }
+            MoveError::NoChecker(i) => write!(f, "no checker on {}", i),
+            MoveError::BarFirst => write!(f, "checkers on the bar must enter first"),
+            MoveError::Blocked(i) => write!(f, "{} is blocked", i),
+            MoveError::NoHit(i) => write!(f, "no blot to hit on {}", i),
+            MoveError::BearOff(i) => write!(
+                f,
+                "cannot bear off from {} with checkers outside the home board",
+                i
+            ),
+            MoveError::TooFar { pips, dice } => write!(
+                f,
+                "moves {} pips but the roll is {}-{}",
+                pips, dice.0, dice.1
+            ),
+            MoveError::MustPlayMore => write!(f, "more of the roll must be played"),
+            MoveError::NotLegal(s) => write!(f, "{} is not legal with this roll", s),
+        }
+    }
+}
+impl std::error::Error for MoveError {}
+
+// (from, to, marked as a hit)
+fn segments(text: &str) -> std::result::Result<Vec<(usize, usize, bool)>, MoveError> {
+    let mut segs = vec![];
+    for token in text.split(|c: char| c.is_whitespace() || c == ',') {
+        if token.is_empty() {
+            continue;
+        }
+        let syntax = || MoveError::Syntax(token.to_owned());
+        let (body, count) = match token.strip_suffix(')') {
+            Some(t) => {
+                let (body, count) = t.split_once('(').ok_or_else(syntax)?;
+                let count: usize = count.parse().map_err(|_| syntax())?;
+                if !(1..=4).contains(&count) {
+                    return Err(syntax());
+                }
+                (body, count)
+            }
+            None => (token, 1),
+        };
+        let points = body
+            .split('/')
+            .map(|s| {
+                let hit = s.ends_with('*');
+                let i = match s.trim_end_matches('*').to_lowercase().as_str() {
+                    "bar" => Some(Pieces::BAR),
+                    "off" => Some(Pieces::GOAL),
+                    s => s.parse().ok().filter(|&i| i <= Pieces::BAR),
+                };
+                i.map(|i| (i, hit)).ok_or_else(syntax)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if points.len() < 2 || points[0].1 {
+            return Err(syntax());
+        }
+        for _ in 0..count {
+            for w in points.windows(2) {
+                segs.push((w[0].0, w[1].0, w[1].1));
+            }
+        }
+    }
+    Ok(segs)
+}
+
+// The checkers each point loses over the move and the points hit on the way, which
+// do not depend on the order the segments are written in.
+fn effect(segs: &[(usize, usize, bool)]) -> ([isize; Pieces::BAR + 1], Vec<usize>) {
+    let mut flow = [0; Pieces::BAR + 1];
+    let mut hits = vec![];
+    for &(from, to, hit) in segs {
+        flow[from] += 1;
+        flow[to] -= 1;
+        if hit {
+            hits.push(to);
+        }
+    }
+    hits.sort_unstable();
+    hits.dedup();
+    (flow, hits)
+}
+
+impl Move {
+    // resolves the notation to one of the legal moves of the board
+    pub fn parse(board: &Board, text: &str) -> std::result::Result<Move, MoveError> {
+        if board.state() != State::ToMove {
+            return Err(MoveError::NotToMove);
+        }
+        let segs = segments(text)?;
+        if let Some(&(from, to, _)) = segs.iter().find(|(from, to, _)| from <= to) {
+            return Err(MoveError::Backward { from, to });
+        }
+        let (flow, marked) = effect(&segs);
+        let moves = board.moves();
+        let same: Vec<(&Move, Vec<usize>)> = moves
+            .iter()
+            .map(|m| (m, effect(&m.0)))
+            .filter(|(_, (f, _))| *f == flow)
+            .map(|(m, (_, hits))| (m, hits))
+            .collect();
+        // as to_str writes it, which tells apart moves that end the same; then the hits
+        // as marked, or else a move making every marked hit
+        let found = same
+            .iter()
+            .find(|(m, _)| m.to_str() == text.trim())
+            .or_else(|| same.iter().find(|(_, hits)| *hits == marked))
+            .or_else(|| {
+                same.iter()
+                    .find(|(_, hits)| marked.iter().all(|h| hits.contains(h)))
+            });
+        if let Some((m, _)) = found {
+            return Ok((*m).clone());
+        }
+
+        // why not: the first segment that cannot be played in the written order
+        let p = board.player.unwrap();
+        let dice = board.dice.0.unwrap();
+        let rules = board.variant.rules();
+        let mut ps = rules.view(&board.pieces, p);
+        let start = ps.get(Pieces::GOAL);
+        let mut pips = 0;
+        for (from, to, hit) in segs {
+            if ps.count(ps.source(from, p), p) == 0 {
+                return Err(MoveError::NoChecker(from));
+            }
+            if from != Pieces::BAR && ps.get(Pieces::BAR).is_some() {
+                return Err(MoveError::BarFirst);
+            }
+            if !rules.open(&ps, to, p) {
+                return Err(MoveError::Blocked(to));
+            }
+            if to == Pieces::GOAL && ps.backman(p) > Pieces::INNER_BOARD {
+                return Err(MoveError::BearOff(from));
+            }
+            if !rules.mov(&mut ps, from, to, p) && hit {
+                return Err(MoveError::NoHit(to));
+            }
+            pips += from - to;
+        }
+        let roll = if dice.0 == dice.1 {
+            4 * dice.0
+        } else {
+            dice.0 + dice.1
+        };
+        let bearoff = ps.get(Pieces::GOAL) != start;
+        if pips > roll && !bearoff {
+            return Err(MoveError::TooFar { pips, dice });
+        }
+        let least = moves
+            .iter()
+            .map(|m| m.0.iter().map(|(from, to, _)| from - to).sum::<usize>())
+            .min()
+            .unwrap_or(0);
+        if pips < least {
+            return Err(MoveError::MustPlayMore);
+        }
+        Err(MoveError::NotLegal(text.trim().to_owned()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::board::{Action, Variant};
+    use crate::dice::{DiceSource, Rng};
+
+    // reads back the notation of every legal move over seeded random games
+    pub(crate) fn parse_every_move(variant: Variant, games: u64) {
+        for seed in 0..games {
+            let mut rng = Rng::new(seed);
+            let mut b = Board::with_variant(variant);
+            while b.state() != State::End && b.state() != State::MatchEnd {
+                let act = match b.state() {
+                    State::Init => Action::InitRoll(rng.init_roll()),
+                    State::ToRoll => Action::Roll(rng.roll()),
+                    State::ToChoose => Action::Choose(rng.die()),
+                    State::ToMove => {
+                        let moves = b.moves();
+                        for m in &moves {
+                            let text = m.to_str();
+                            let parsed = Move::parse(&b, &text)
+                                .unwrap_or_else(|e| panic!("{} at {}: {}", text, b.xgid(), e));
+                            assert_eq!(parsed.to_str(), text, "at {}", b.xgid());
+                        }
+                        Action::Move(moves[rng.below(moves.len())].clone())
+                    }
+                    _ => Action::NoDouble,
+                };
+                b.act(&act);
+            }
+        }
+    }
+
+    fn board(dice: Dice) -> Board {
+        let mut b = Board::new();
+        b.act(&Action::InitRoll(dice));
+        b
+    }
+
+    #[test]
+    fn parse() {
+        let b = board(Dice(3, 1));
+        let m = Move::parse(&b, "8/5 6/5").unwrap();
+        assert_eq!(m.to_str(), "8/5 6/5");
+        assert_eq!(Move::parse(&b, "6/5, 8/5").unwrap(), m);
+        assert_eq!(Move::parse(&b, "24/21/20").unwrap().to_str(), "24/20");
+        assert_eq!(Move::parse(&b, "24/20").unwrap().to_str(), "24/20");
+
+        let b = Board::from_xg_id("XGID=-b----E-C---eE---c-e----B-:0:0:1:66:0:0:0:5:10").unwrap();
+        let m = Move::parse(&b, "13/7(2) 24/18(2)").unwrap();
+        assert_eq!(m.to_str(), "24/18 24/18 13/7 13/7");
+        assert_eq!(
+            Move::parse(&b, "24/18/12 13/7/1").unwrap_err(),
+            MoveError::Blocked(12)
+        );
+    }
+
+    #[test]
+    fn bar_and_off() {
+        let b = Board::from_xg_id("XGID=-A----------------------a-:0:0:1:64:0:0:0:1:10").unwrap();
+        assert_eq!(Move::parse(&b, "1/off").unwrap().to_str(), "1/0");
+        let b = Board::from_xg_id("XGID=-----BB-----------------b-:0:0:1:66:0:0:0:1:10").unwrap();
+        assert_eq!(
+            Move::parse(&b, "6/off(2) 5/off(2)").unwrap().to_str(),
+            "6/0 6/0 5/0 5/0"
+        );
+        assert_eq!(
+            Move::parse(&b, "24/18").unwrap_err(),
+            MoveError::NoChecker(24)
+        );
+
+        let mut b = board(Dice(3, 1));
+        b.act(&Action::Move(Move(vec![(24, 21, false), (21, 20, false)])));
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(5, 4)));
+        let m = Move::parse(&b, "13/8 13/9").unwrap();
+        b.act(&Action::Move(m));
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(4, 2)));
+        let m = Move::parse(&b, "13/9 13/11").unwrap();
+        b.act(&Action::Move(m));
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(4, 2)));
+        // White's blot on 20 is Black's 5
+        let m = Move::parse(&b, "9/5* 6/4").unwrap();
+        b.act(&Action::Move(m));
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(6, 5)));
+        assert_eq!(
+            Move::parse(&b, "13/7 13/8").unwrap_err(),
+            MoveError::BarFirst
+        );
+        assert_eq!(
+            Move::parse(&b, "bar/20* 24/18").unwrap().to_str(),
+            "25/20* 24/18"
+        );
+    }
+
+    #[test]
+    fn every_move() {
+        parse_every_move(Variant::Standard, 3);
+    }
+
+    #[test]
+    fn out_of_order() {
+        // a hit on the point another checker then joins, as to_str writes it
+        let b = Board::from_xgid("XGID=-a----EaC-AAdCa--aae---aB---:0:0:1:14:0:0:0:5:10").unwrap();
+        let m = Move::parse(&b, "11/7 8/7*").unwrap();
+        assert_eq!(m.to_str(), "11/7 8/7*");
+    }
+
+    #[test]
+    fn errors() {
+        let b = board(Dice(3, 1));
+        let err = |s: &str| Move::parse(&b, s).unwrap_err();
+        assert_eq!(err("8-5"), MoveError::Syntax("8-5".to_owned()));
+        assert_eq!(err("6/off(5)"), MoveError::Syntax("6/off(5)".to_owned()));
+        assert_eq!(err("5/8"), MoveError::Backward { from: 5, to: 8 });
+        assert_eq!(err("6/5*"), MoveError::NoHit(5));
+        assert_eq!(err("6/1"), MoveError::Blocked(1));
+        assert_eq!(err("6/off"), MoveError::BearOff(6));
+        assert_eq!(
+            err("13/7"),
+            MoveError::TooFar {
+                pips: 6,
+                dice: Dice(3, 1)
+            }
+        );
+        assert_eq!(err("8/5"), MoveError::MustPlayMore);
+        assert_eq!(
+            err("13/11 6/4"),
+            MoveError::NotLegal("13/11 6/4".to_owned())
+        );
+        assert_eq!(
+            Move::parse(&Board::new(), "8/5 6/5"),
+            Err(MoveError::NotToMove)
+        );
+    }
+}
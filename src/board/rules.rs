@@ -0,0 +1,557 @@
+// How checkers move and how a finished game is scored, for each family of variants.
+// Positions are seen from the mover: its checkers travel from its 24 point down to 1
+// and are borne off to 0, and the opponent's goal is at BLACK_GOAL.
+use super::{Move, Pieces, Player};
+
+pub trait Rules {
+    // the position seen from p; seeing it again from p gives the position back
+    fn view(&self, pieces: &Pieces, p: Player) -> Pieces {
+        pieces.reversed(p)
+    }
+    // whether a checker of p may land on the point
+    fn open(&self, ps: &Pieces, to: usize, p: Player) -> bool;
+    // plays one checker, returning whether it hit or pinned
+    fn mov(&self, ps: &mut Pieces, from: usize, to: usize, p: Player) -> bool;
+    // whether a checker may leave the point, after the steps played so far with the dice
+    fn may_leave(
+        &self,
+        _ps: &Pieces,
+        _from: usize,
+        _p: Player,
+        _dice: &[usize],
+        _played: &[(usize, usize, bool)],
+    ) -> bool {
+        true
+    }
+    // whether p may end its move in the position
+    fn legal(&self, _ps: &Pieces, _p: Player) -> bool {
+        true
+    }
+    // points won by p, 0 while the game goes on
+    fn goal(&self, pieces: &Pieces, p: Player) -> usize;
+    fn cube(&self) -> bool {
+        true
+    }
+    // whether a 1-2 earns a double of choice and another roll
+    fn bonus(&self) -> bool {
+        false
+    }
+    // the moves of p with the dice played in this order
+    fn listup(&self, ps: &Pieces, dice: &[usize], p: Player) -> Vec<Move> {
+        listup(self, ps, dice, dice, p, &mut vec![])
+    }
+}
+
+fn listup<R: Rules + ?Sized>(
+    rules: &R,
+    ps: &Pieces,
+    dice: &[usize],
+    all: &[usize],
+    p: Player,
+    played: &mut Vec<(usize, usize, bool)>,
+) -> Vec<Move> {
+    let done = || {
+        if rules.legal(ps, p) {
+            vec![Move(vec![])]
+        } else {
+            vec![]
+        }
+    };
+    let backman = ps.backman(p);
+    if dice.is_empty() || backman == 0 {
+        return done();
+    }
+    let (d, dice) = dice.split_at(1);
+    let mut d = d[0];
+    let mut mov = vec![];
+    for i in (1..=Pieces::BOARD_SIZE).rev() {
+        // pieces on the bar must enter first
+        if ps.count(Pieces::BAR, p) > 0 && i != Pieces::BAR {
+            continue;
+        }
+        // backman can be bearoff over rolled
+        if i == backman && backman <= Pieces::INNER_BOARD && i < d {
+            d = backman;
+        }
+        // bareoff is not allowed if backman dose not reached
+        if backman > Pieces::INNER_BOARD && i == d {
+            continue;
+        }
+        // too big move
+        if i < d {
+            continue;
+        }
+        let to = i - d;
+        let own = ps.count(ps.source(i, p), p) > 0;
+        if !own || !rules.open(ps, to, p) || !rules.may_leave(ps, i, p, all, played) {
+            continue;
+        }
+        let mut np = ps.clone();
+        let hit = rules.mov(&mut np, i, to, p);
+        played.push((i, to, hit));
+        for mut m in listup(rules, &np, dice, all, p, played) {
+            m.0.insert(0, (i, to, hit));
+            mov.push(m);
+        }
+        played.pop();
+    }
+    // the rest of the dice cannot be played
+    if mov.is_empty() {
+        return done();
+    }
+    mov
+}
+
+// 1 for a single game, 2 once p is off before the opponent has borne off a checker
+fn single_or_gammon(ps: &Pieces, p: Player) -> usize {
+    if ps.backman(p) > 0 {
+        0
+    } else if ps.get(Pieces::BLACK_GOAL).is_some() {
+        1
+    } else {
+        2
+    }
+}
+
+pub struct Backgammon;
+impl Rules for Backgammon {
+    fn open(&self, ps: &Pieces, to: usize, p: Player) -> bool {
+        match ps.get(to) {
+            Some((o, c)) => o == p || c == 1,
+            None => true,
+        }
+    }
+    fn mov(&self, ps: &mut Pieces, from: usize, to: usize, p: Player) -> bool {
+        let hit = ps.hittable(to, p);
+        ps.mov(ps.source(from, p), to, p);
+        hit
+    }
+    fn goal(&self, pieces: &Pieces, p: Player) -> usize {
+        pieces.goal(p)
+    }
+}
+
+// the same moves without the cube, and a backgammon only counts as a gammon
+pub struct Tavla;
+impl Rules for Tavla {
+    fn open(&self, ps: &Pieces, to: usize, p: Player) -> bool {
+        Backgammon.open(ps, to, p)
+    }
+    fn mov(&self, ps: &mut Pieces, from: usize, to: usize, p: Player) -> bool {
+        Backgammon.mov(ps, from, to, p)
+    }
+    fn goal(&self, pieces: &Pieces, p: Player) -> usize {
+        pieces.goal(p).min(2)
+    }
+    fn cube(&self) -> bool {
+        false
+    }
+}
+
+// A checker landing on a blot pins it until the point is left. Pinning the opponent's
+// last checker on its starting point (its mother) wins a gammon at once.
+pub struct Plakoto;
+impl Rules for Plakoto {
+    fn open(&self, ps: &Pieces, to: usize, p: Player) -> bool {
+        match ps.get(to) {
+            Some((o, c)) => o == p || (c == 1 && !ps.pinned(to)),
+            None => true,
+        }
+    }
+    fn mov(&self, ps: &mut Pieces, from: usize, to: usize, p: Player) -> bool {
+        let o = p.opponent();
+        ps.add(from, p, -1);
+        if ps.get(from).is_none() && ps.pinned(from) {
+            ps.set_pinned(from, false);
+            ps.set(from, o, 1);
+        }
+        let pin = to != Pieces::GOAL && ps.get(to) == Some((o, 1));
+        if pin {
+            ps.set(to, p, 1);
+            ps.set_pinned(to, true);
+        } else {
+            ps.add(to, p, 1);
+        }
+        pin
+    }
+    fn goal(&self, pieces: &Pieces, p: Player) -> usize {
+        let ps = pieces.reversed(p);
+        let o = p.opponent();
+        let mother = |i, q| ps.pinned(i) && matches!(ps.get(i), Some((t, _)) if t == q);
+        // both mothers pinned: the game goes on
+        if mother(1, p) && !mother(Pieces::BAR - 1, o) {
+            return 2;
+        }
+        single_or_gammon(&ps, p)
+    }
+    fn cube(&self) -> bool {
+        false
+    }
+}
+
+// Checkers start off the board and enter as from the bar, while others may move on.
+pub struct AceyDeucey;
+impl Rules for AceyDeucey {
+    fn open(&self, ps: &Pieces, to: usize, p: Player) -> bool {
+        Backgammon.open(ps, to, p)
+    }
+    fn mov(&self, ps: &mut Pieces, from: usize, to: usize, p: Player) -> bool {
+        Backgammon.mov(ps, from, to, p)
+    }
+    fn goal(&self, pieces: &Pieces, p: Player) -> usize {
+        pieces.goal(p)
+    }
+    fn bonus(&self) -> bool {
+        true
+    }
+}
+
+// p's point i for the opponent, when both sides move the same way round the board
+fn across(i: usize) -> usize {
+    (i + 11) % 24 + 1
+}
+
+// Nothing is hit: a single checker holds a point. Before a second checker leaves the
+// start, the first must have passed the opponent's start.
+pub struct Fevga;
+impl Rules for Fevga {
+    fn view(&self, pieces: &Pieces, p: Player) -> Pieces {
+        if p == Player::White {
+            pieces.clone()
+        } else {
+            pieces.remap(across)
+        }
+    }
+    fn open(&self, ps: &Pieces, to: usize, p: Player) -> bool {
+        match ps.get(to) {
+            Some((o, _)) => o == p,
+            None => true,
+        }
+    }
+    fn mov(&self, ps: &mut Pieces, from: usize, to: usize, p: Player) -> bool {
+        ps.add(from, p, -1);
+        ps.add(to, p, 1);
+        false
+    }
+    fn may_leave(
+        &self,
+        ps: &Pieces,
+        from: usize,
+        p: Player,
+        _dice: &[usize],
+        _played: &[(usize, usize, bool)],
+    ) -> bool {
+        from != 24
+            || ps.count(24, p) == Pieces::MAX_PIECES
+            || (0..across(24)).any(|i| ps.count(i, p) > 0)
+    }
+    fn legal(&self, ps: &Pieces, p: Player) -> bool {
+        !traps(ps, p)
+    }
+    fn goal(&self, pieces: &Pieces, p: Player) -> usize {
+        single_or_gammon(&self.view(pieces, p), p)
+    }
+    fn cube(&self) -> bool {
+        false
+    }
+}
+
+// Only one checker a turn leaves the start (the head), two with 66, 44 or 33 from the
+// full head.
+pub struct LongNardy;
+impl Rules for LongNardy {
+    fn view(&self, pieces: &Pieces, p: Player) -> Pieces {
+        Fevga.view(pieces, p)
+    }
+    fn open(&self, ps: &Pieces, to: usize, p: Player) -> bool {
+        Fevga.open(ps, to, p)
+    }
+    fn mov(&self, ps: &mut Pieces, from: usize, to: usize, p: Player) -> bool {
+        Fevga.mov(ps, from, to, p)
+    }
+    fn may_leave(
+        &self,
+        ps: &Pieces,
+        from: usize,
+        p: Player,
+        dice: &[usize],
+        played: &[(usize, usize, bool)],
+    ) -> bool {
+        if from != 24 {
+            return true;
+        }
+        let left = played.iter().filter(|m| m.0 == 24).count();
+        let full = ps.count(24, p) + left == Pieces::MAX_PIECES;
+        let limit = if full && dice.len() == 4 && [3, 4, 6].contains(&dice[0]) {
+            2
+        } else {
+            1
+        };
+        left < limit
+    }
+    fn legal(&self, ps: &Pieces, p: Player) -> bool {
+        !traps(ps, p)
+    }
+    fn goal(&self, pieces: &Pieces, p: Player) -> usize {
+        single_or_gammon(&self.view(pieces, p), p)
+    }
+    fn cube(&self) -> bool {
+        false
+    }
+}
+
+// whether p holds six points in a row with none of the opponent's checkers past them
+fn traps(ps: &Pieces, p: Player) -> bool {
+    let o = p.opponent();
+    // the opponent's points, in its own numbering
+    let held = |j: usize| ps.count(across(j), p) > 0;
+    let theirs = |j: usize| ps.count(across(j), o) > 0;
+    let off = ps.get(Pieces::BLACK_GOAL).is_some();
+    (1..=Pieces::BAR - 6).any(|j| (j..j + 6).all(held) && !off && !(1..j).any(theirs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::{mat, notation, Action, Board, Dice, State, Variant, XgidError};
+
+    // plays the first move each time with dice from a fixed sequence
+    fn play_out(variant: Variant) -> Board {
+        let mut b = Board::with_variant(variant);
+        b.act(&Action::InitRoll(Dice(3, 1)));
+        let mut seed = 7usize;
+        for _ in 0..5000 {
+            match b.state() {
+                State::ToDouble => b.act(&Action::NoDouble),
+                State::ToRoll => {
+                    seed = (seed * 1103515245 + 12345) % (1 << 31);
+                    let r = (seed >> 16) % 36;
+                    b.act(&Action::Roll(Dice(r / 6 + 1, r % 6 + 1)))
+                }
+                State::ToMove | State::ToChoose => b.act(&b.actions()[0].clone()),
+                _ => return b,
+            }
+            for p in [Player::White, Player::Black] {
+                let ps = variant.rules().view(&b.pieces, p);
+                // a pinned checker is under one of the other side's
+                let on = (0..Pieces::BLACK_GOAL)
+                    .chain([Pieces::START])
+                    .map(|i| ps.count(i, p) + (ps.pinned(i) && ps.count(i, p) == 0) as usize)
+                    .sum::<usize>();
+                assert_eq!(on, Pieces::MAX_PIECES, "{:?}\n{}", variant, b);
+            }
+        }
+        panic!("{:?} does not end", variant)
+    }
+
+    #[test]
+    fn play_variants() {
+        for variant in [
+            Variant::Tavla,
+            Variant::Plakoto,
+            Variant::Fevga,
+            Variant::LongNardy,
+            Variant::AceyDeucey,
+        ] {
+            let b = play_out(variant);
+            let points = b.result.unwrap().score;
+            assert!(points == 1 || points == 2, "{:?} {}", variant, points);
+        }
+    }
+
+    #[test]
+    fn notation() {
+        // a pin is written as a hit, and reads back even when the point is left again
+        let xgid = "XGID=-iA-a---a-A---A-aa-a-A--J---:0:0:0:11:0:0:0:5:10:P/14/19";
+        let b = Board::from_xgid(xgid).unwrap();
+        assert_eq!(Move::parse(&b, "9/6*/5").unwrap().to_str(), "9/6*/5");
+        for v in [
+            Variant::Tavla,
+            Variant::Plakoto,
+            Variant::Fevga,
+            Variant::LongNardy,
+        ] {
+            notation::test::parse_every_move(v, 1);
+            mat::test::random_matches(v, 3);
+        }
+    }
+
+    #[test]
+    fn tavla() {
+        let mut b = Board::with_variant(Variant::Tavla);
+        b.act(&Action::InitRoll(Dice(3, 1)));
+        b.act(&b.actions()[0].clone());
+        assert_eq!(b.actions(), vec![Action::NoDouble]);
+
+        // a backgammon only scores two
+        let xgid = "XGID=-A---a-------------n------:0:0:1:21:0:0:0:0:10:T";
+        let mut b = Board::from_xgid(xgid).unwrap();
+        b.act(&b.actions()[0].clone());
+        assert_eq!(b.result.unwrap().score, 2);
+    }
+
+    #[test]
+    fn plakoto() {
+        let b = Board::with_variant(Variant::Plakoto);
+        assert_eq!(b.pieces.get(24), Some((Player::White, 15)));
+        assert_eq!(b.pieces.get(1), Some((Player::Black, 15)));
+
+        // White pins the blot on 20, which then cannot move
+        let xgid = "XGID=--------------------a---O:0:0:1:42:0:0:0:0:10:P";
+        let mut b = Board::from_xgid(xgid).unwrap();
+        assert_eq!(b.pieces.borne_off(Player::Black), 14);
+        let pin = Move(vec![(24, 20, true), (24, 22, false)]);
+        assert!(b.moves().contains(&pin));
+        b.act(&Action::Move(pin));
+        assert_eq!(b.pieces.get(20), Some((Player::White, 1)));
+        assert!(b.pieces.pinned(20));
+        assert!(b.xgid().ends_with(":P/20"), "{}", b.xgid());
+        assert_eq!(Board::from_xgid(&b.xgid()).unwrap(), b);
+        assert!(b.xg_id().ends_with(":P/20"), "{}", b.xg_id());
+        assert_eq!(Board::from_xg_id(&b.xg_id()).unwrap(), b);
+        assert!(b.position_id().is_err());
+
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(2, 1)));
+        assert_eq!(b.moves(), vec![Move::DANCE]);
+        b.act(&Action::Move(Move::DANCE));
+
+        // leaving the point frees the pinned checker
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(6, 5)));
+        let free = Move(vec![(22, 17, false), (20, 14, false)]);
+        assert!(b.moves().contains(&free));
+        b.act(&Action::Move(free));
+        assert_eq!(b.pieces.get(20), Some((Player::Black, 1)));
+        assert!(!b.pieces.pinned(20));
+
+        // pinning the mother wins a gammon at once
+        let xgid = "XGID=-a--A-------------------N:0:0:1:31:0:0:0:0:10:P";
+        let mut b = Board::from_xgid(xgid).unwrap();
+        let pin = Move(vec![(24, 23, false), (4, 1, true)]);
+        assert!(b.moves().contains(&pin));
+        b.act(&Action::Move(pin));
+        assert_eq!(b.state(), State::End);
+        assert_eq!(b.result.unwrap().score, 2);
+
+        assert!(matches!(
+            Board::from_xgid(&format!("{}/3", xgid)),
+            Err(XgidError::Inconsistent(_))
+        ));
+    }
+
+    #[test]
+    fn fevga() {
+        let b = Board::with_variant(Variant::Fevga);
+        assert_eq!(b.pieces.get(24), Some((Player::White, 15)));
+        assert_eq!(b.pieces.get(12), Some((Player::Black, 15)));
+        assert_eq!(
+            Fevga.view(&b.pieces, Player::Black).get(24),
+            Some((Player::Black, 15))
+        );
+
+        // the first checker must pass Black's start before another leaves
+        let mut b = Board::with_variant(Variant::Fevga);
+        b.act(&Action::InitRoll(Dice(6, 5)));
+        assert_eq!(
+            b.moves(),
+            vec![Move(vec![(24, 18, false), (18, 13, false)])]
+        );
+
+        // a single checker holds a point
+        let xgid = "XGID=-----------------------aO:0:0:1:21:0:0:0:0:10:F";
+        let b = Board::from_xgid(xgid).unwrap();
+        assert_eq!(
+            b.moves(),
+            vec![Move(vec![(24, 22, false), (22, 21, false)])]
+        );
+    }
+
+    #[test]
+    fn long_nardy() {
+        let mut b = Board::with_variant(Variant::LongNardy);
+        b.act(&Action::InitRoll(Dice(6, 5)));
+        assert_eq!(
+            b.moves(),
+            vec![Move(vec![(24, 18, false), (18, 13, false)])]
+        );
+
+        // two checkers leave the full head with 66, and Black's head blocks the rest
+        let mut b = Board::with_variant(Variant::LongNardy);
+        b.player = Some(Player::White);
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(6, 6)));
+        let double = Move(vec![(24, 18, false), (24, 18, false)]);
+        assert_eq!(b.moves(), vec![double]);
+
+        // no six-point block with all of Black's checkers behind it
+        let xgid = "XGID=------AAAAAAo-----------I:0:0:1:21:0:0:0:0:10:L";
+        let b = Board::from_xgid(xgid).unwrap();
+        assert!(traps(&b.pieces, Player::White));
+        let b = Board::from_xgid(&xgid.replace("AAAAAAo--", "AAAAA-o-A")).unwrap();
+        let moves = b.moves();
+        assert!(!moves.is_empty());
+        assert!(!moves.contains(&Move(vec![(14, 13, false), (13, 11, false)])));
+    }
+
+    #[test]
+    fn acey_deucey() {
+        let mut b = Board::with_variant(Variant::AceyDeucey);
+        assert_eq!(b.pieces.get(Pieces::START), Some((Player::White, 15)));
+        assert_eq!(b.pieces.get(Pieces::BLACK_START), Some((Player::Black, 15)));
+        assert_eq!(b.pieces.pips(Player::White), 375);
+        mat::test::random_matches(Variant::AceyDeucey, 5);
+
+        // a 1-2 is followed by a double of choice and then another turn
+        b.act(&Action::InitRoll(Dice(2, 1)));
+        b.act(&b.actions()[0].clone());
+        assert_eq!(b.state(), State::ToChoose);
+        assert_eq!(b.player, Some(Player::White));
+        assert_eq!(b.actions(), (1..=6).map(Action::Choose).collect::<Vec<_>>());
+        assert_eq!(Board::from_xgid(&b.xgid()).unwrap(), b);
+        b.act(&Action::Choose(5));
+        assert_eq!(b.state(), State::ToMove);
+        assert!(
+            b.xgid().ends_with(":55:0:0:0:1:10:A+/W13/B15"),
+            "{}",
+            b.xgid()
+        );
+        assert_eq!(Board::from_xgid(&b.xgid()).unwrap(), b);
+        b.act(&b.actions()[0].clone());
+        assert_eq!(b.state(), State::ToDouble);
+        assert_eq!(b.player, Some(Player::White));
+        assert_eq!(Board::from_xgid(&b.xgid()).unwrap(), b);
+
+        // any other roll passes the turn
+        b.act(&Action::NoDouble);
+        b.act(&Action::Roll(Dice(3, 1)));
+        b.act(&b.actions()[0].clone());
+        assert_eq!(b.player, Some(Player::Black));
+
+        // a 1-2 that cannot be played earns nothing
+        let xgid = "XGID=-----------------------bb--:0:0:1:21:0:0:0:0:10:A/W15/B11";
+        let mut b = Board::from_xgid(xgid).unwrap();
+        b.act(&b.actions()[0].clone());
+        assert_eq!(b.state(), State::ToDouble);
+        assert_eq!(b.player, Some(Player::Black));
+
+        // the checkers yet to enter are counted in the variant field, only in acey-deucey
+        assert_eq!(
+            Board::from_xgid("XGID=--------------------------:0:0:1:21:0:0:0:0:10:A/W16")
+                .unwrap_err(),
+            XgidError::TooManyCheckers {
+                player: Player::White,
+                count: 16
+            }
+        );
+        assert_eq!(
+            Board::from_xgid("XGID=--------------------------:0:0:1:21:0:0:0:0:10:P/W15")
+                .unwrap_err(),
+            XgidError::BadVariant("P/W15".to_owned())
+        );
+        assert_eq!(
+            Board::from_xgid("XGID=--------------------------:0:0:1:21:0:0:0:0:10:T+").unwrap_err(),
+            XgidError::BadVariant("T+".to_owned())
+        );
+    }
+}
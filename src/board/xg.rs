@@ -0,0 +1,371 @@
+// eXtreme Gammon compatible XGID
+// XGID=position:cube:cube position:turn:dice:score white:score black:crawford or jacoby/beaver:length:max cube
+// The position is 26 chars: Black's bar, points 1 to 24 and White's bar.
+// While doubled the turn is the doubler, and the cube is written as it was before the double;
+// the dice are D, or B and R after a beaver and a raccoon.
+// Any variant but the standard game follows in an extra field, as for Board::xgid.
+use super::{Board, Cube, DiceRoll, Match, Pieces, Player, State, VariantField, XgidError};
+
+const POSITION_LEN: usize = 26;
+
+fn xg_player(p: Option<Player>) -> &'static str {
+    match p {
+        Some(Player::White) => "1",
+        Some(Player::Black) => "-1",
+        None => "0",
+    }
+}
+
+impl Board {
+    pub fn xg_id(&self) -> String {
+        let mut s = "XGID=".to_string();
+        let mut order = vec![Pieces::BLACK_BAR];
+        order.extend(1..=Pieces::BAR);
+        for i in order {
+            s.push(match self.pieces.get(i) {
+                Some((Player::White, c)) => (b'A' + c as u8 - 1) as char,
+                Some((Player::Black, c)) => (b'a' + c as u8 - 1) as char,
+                None => '-',
+            });
+        }
+
+        let state = self.state();
+        let level = self.cube.level - self.cube.beavers;
+        let (turn, position) = if state == State::Doubled {
+            let doubler = Some(self.on_roll());
+            let position = if level == 0 { None } else { doubler };
+            (doubler, position)
+        } else {
+            (self.player, self.cube.position)
+        };
+        let dice = match state {
+            State::ToMove => self.dice.to_str(),
+            State::Doubled => ["D", "B", "R"][self.cube.beavers].to_owned(),
+            _ => "00".to_owned(),
+        };
+        let flags = if self.game.is_money() {
+            self.game.jacoby as usize + 2 * self.game.beaver as usize
+        } else {
+            self.game.crawford as usize
+        };
+        s.push_str(&format!(
+            ":{}:{}:{}:{}:{}:{}:{}:{}:{}",
+            level,
+            xg_player(position),
+            xg_player(turn),
+            dice,
+            self.game.score.0,
+            self.game.score.1,
+            flags,
+            self.game.length,
+            self.cube.max_level
+        ));
+        if let Some(v) = self.variant_field() {
+            s.push(':');
+            s.push_str(&v);
+        }
+        s
+    }
+
+    pub fn from_xg_id(id: &str) -> std::result::Result<Board, XgidError> {
+        let i = id.find('=').ok_or(XgidError::MissingPrefix)?;
+        let id: Vec<&str> = id[i + 1..].split(':').collect();
+        if !(9..=11).contains(&id.len()) {
+            return Err(XgidError::FieldCount(id.len()));
+        }
+        let extra = VariantField::parse(id.get(10).copied())?;
+        let number = |field: usize| {
+            id[field]
+                .parse::<usize>()
+                .map_err(|_| XgidError::BadNumber {
+                    field,
+                    value: id[field].to_owned(),
+                })
+        };
+        let player = |field: usize| match id[field] {
+            "1" => Ok(Some(Player::White)),
+            "-1" => Ok(Some(Player::Black)),
+            "0" => Ok(None),
+            s => Err(s.to_owned()),
+        };
+
+        let len = id[0].chars().count();
+        if len != POSITION_LEN {
+            return Err(XgidError::PositionLength(len));
+        }
+        let mut pieces = Pieces::empty();
+        let mut white = 0;
+        let mut black = 0;
+        for (pos, b) in id[0].chars().enumerate() {
+            let (p, c) = match b {
+                '-' => continue,
+                'A'..='O' if pos != 0 => (Player::White, (b as u8 + 1 - b'A') as usize),
+                'a'..='o' if pos != Pieces::BAR => (Player::Black, (b as u8 + 1 - b'a') as usize),
+                _ => return Err(XgidError::BadChecker { pos, found: b }),
+            };
+            let i = if pos == 0 { Pieces::BLACK_BAR } else { pos };
+            if p == Player::White {
+                white += c;
+            } else {
+                black += c;
+            }
+            pieces.set(i, p, c);
+        }
+        extra.apply(&mut pieces, white, black)?;
+
+        let level = number(1)?;
+        let max_level = if id.len() >= 10 {
+            number(9)?
+        } else {
+            Cube::DEFAULT_MAX_LEVEL
+        };
+        if level > max_level {
+            return Err(XgidError::Inconsistent("cube level above the maximum"));
+        }
+        let position = player(2).map_err(XgidError::BadCubePosition)?;
+        let turn = player(3).map_err(XgidError::BadPlayer)?;
+        let beavers = match id[4] {
+            "B" => 1,
+            "R" => 2,
+            _ => 0,
+        };
+        let doubled = beavers > 0 || id[4] == "D";
+        let dice = match id[4] {
+            "D" | "B" | "R" | "00" => DiceRoll::new(),
+            s => {
+                let d: Vec<usize> = s
+                    .chars()
+                    .filter_map(|c| c.to_digit(10))
+                    .filter(|d| (1..=6).contains(d))
+                    .map(|d| d as usize)
+                    .collect();
+                if d.len() != 2 || s.len() != 2 {
+                    return Err(XgidError::InvalidDice(s.to_owned()));
+                }
+                DiceRoll::roll(d[0], d[1])
+            }
+        };
+        if turn.is_none() && (dice.0.is_some() || doubled || extra.bonus) {
+            return Err(XgidError::Inconsistent("dice or double without a player"));
+        }
+        let (player, position) = if doubled {
+            if position.is_some() && position != turn {
+                return Err(XgidError::Inconsistent("double without access to the cube"));
+            }
+            // the cube is with whoever doubled last, and the other player decides
+            let other = turn.map(|p| p.opponent());
+            match beavers {
+                0 => (other, other),
+                1 => (turn, other),
+                _ => (other, turn),
+            }
+        } else {
+            (turn, position)
+        };
+        let cube = Cube {
+            position,
+            level: level + beavers,
+            doubled,
+            max_level,
+            beavers,
+        };
+        if cube.level > max_level {
+            return Err(XgidError::Inconsistent("cube level above the maximum"));
+        }
+
+        let length = number(8)?;
+        let flags = number(7)?;
+        let game = if length == 0 {
+            if flags > 3 {
+                return Err(XgidError::BadNumber {
+                    field: 7,
+                    value: id[7].to_owned(),
+                });
+            }
+            Match {
+                score: (number(5)?, number(6)?),
+                ..Match::money(flags & 1 == 1, flags & 2 == 2)
+            }
+        } else {
+            if flags > 1 {
+                return Err(XgidError::BadNumber {
+                    field: 7,
+                    value: id[7].to_owned(),
+                });
+            }
+            Match {
+                score: (number(5)?, number(6)?),
+                crawford: flags == 1,
+                ..Match::with_length(length)
+            }
+        };
+        if !game.is_money() && (game.score.0 > game.length || game.score.1 > game.length) {
+            return Err(XgidError::Inconsistent("score above the match length"));
+        }
+        if beavers > 0 && !game.beaver {
+            return Err(XgidError::Inconsistent("beaver without the beaver rule"));
+        }
+
+        Ok(Board {
+            pieces,
+            cube,
+            player,
+            to_roll: !doubled && cube.position == player.map(|p| p.opponent()),
+            dice,
+            game,
+            result: None,
+            variant: extra.variant,
+            bonus: extra.bonus,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::{Action, Dice, Variant};
+
+    #[test]
+    fn opening() {
+        let id = "XGID=-b----E-C---eE---c-e----B-:0:0:1:52:0:0:0:5:10";
+        let b = Board::from_xg_id(id).unwrap();
+        assert_eq!(b.pieces, Board::new().pieces);
+        assert_eq!(b.player, Some(Player::White));
+        assert_eq!(b.dice, DiceRoll::roll(5, 2));
+        assert_eq!(b.game.length, 5);
+        assert_eq!(b.xg_id(), id);
+    }
+
+    #[test]
+    fn bar() {
+        let id = "XGID=a-B-BBBB----------------bA:0:0:-1:41:2:3:1:5:10";
+        let b = Board::from_xg_id(id).unwrap();
+        assert_eq!(b.pieces.get(Pieces::BLACK_BAR), Some((Player::Black, 1)));
+        assert_eq!(b.pieces.get(Pieces::BAR), Some((Player::White, 1)));
+        assert_eq!(b.pieces.get(Pieces::GOAL), Some((Player::White, 4)));
+        assert_eq!(b.pieces.get(Pieces::BLACK_GOAL), Some((Player::Black, 12)));
+        assert_eq!(b.player, Some(Player::Black));
+        assert!(b.game.crawford);
+        assert_eq!(b.xg_id(), id);
+    }
+
+    #[test]
+    fn money() {
+        let id = "XGID=-b----E-C---eE---c-e----B-:1:1:-1:00:3:1:3:0:8";
+        let b = Board::from_xg_id(id).unwrap();
+        assert!(b.game.is_money());
+        assert!(b.game.jacoby && b.game.beaver);
+        assert_eq!(b.game.score, (3, 1));
+        assert_eq!(b.cube.max_level, 8);
+        assert_eq!(b.state(), State::ToRoll);
+        assert_eq!(b.xg_id(), id);
+
+        let b = Board::from_xg_id("XGID=-b----E-C---eE---c-e----B-:0:0:1:00:0:0:1:0").unwrap();
+        assert!(b.game.jacoby && !b.game.beaver);
+        assert_eq!(b.cube.max_level, Cube::DEFAULT_MAX_LEVEL);
+    }
+
+    #[test]
+    fn doubled() {
+        let mut b = Board::new();
+        b.game.length = 5;
+        b.init_roll(Dice(5, 6));
+        b.act(&b.actions()[0].clone());
+        b.act(&Action::Double);
+        let id = b.xg_id();
+        assert!(id.ends_with(":0:0:1:D:0:0:0:5:10"), "{}", id);
+        assert_eq!(Board::from_xg_id(&id).unwrap(), b);
+
+        b.act(&Action::Take);
+        b.act(&Action::Roll(Dice(3, 1)));
+        b.act(&b.actions()[0].clone());
+        b.act(&Action::Double);
+        let id = b.xg_id();
+        assert!(id.ends_with(":1:-1:-1:D:0:0:0:5:10"), "{}", id);
+        assert_eq!(Board::from_xg_id(&id).unwrap(), b);
+    }
+
+    #[test]
+    fn beavers() {
+        let mut b = Board::new();
+        b.game = Match::money(false, true);
+        b.init_roll(Dice(5, 6));
+        b.act(&b.actions()[0].clone());
+        b.act(&Action::Double);
+        b.act(&Action::Beaver);
+        let id = b.xg_id();
+        assert!(id.ends_with(":0:0:1:B:0:0:2:0:10"), "{}", id);
+        let g = Board::from_xg_id(&id).unwrap();
+        assert_eq!(g, b);
+        assert_eq!(g.actions(), vec![Action::Take, Action::Raccoon]);
+
+        b.act(&Action::Raccoon);
+        let id = b.xg_id();
+        assert!(id.ends_with(":0:0:1:R:0:0:2:0:10"), "{}", id);
+        let g = Board::from_xg_id(&id).unwrap();
+        assert_eq!(g, b);
+        assert_eq!(g.actions(), vec![Action::Take]);
+
+        assert_eq!(
+            Board::from_xg_id(&id.replace(":2:0:10", ":0:0:10")),
+            Err(XgidError::Inconsistent("beaver without the beaver rule"))
+        );
+    }
+
+    #[test]
+    fn variants() {
+        let mut b = Board::with_variant(Variant::Hypergammon(3));
+        b.init_roll(Dice(5, 6));
+        let id = b.xg_id();
+        assert!(id.ends_with(":0:0:0:1:10:H3"), "{}", id);
+        let g = Board::from_xg_id(&id).unwrap();
+        assert_eq!(g, b);
+        assert_eq!(g.pieces.borne_off(Player::White), 0);
+
+        let mut b = Board::with_variant(Variant::Plakoto);
+        b.init_roll(Dice(5, 6));
+        b.act(&b.actions()[0].clone());
+        assert_eq!(Board::from_xg_id(&b.xg_id()).unwrap(), b);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |id: &str| Board::from_xg_id(id).unwrap_err();
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e----B---:0:0:1:52:0:0:0:5:10"),
+            XgidError::PositionLength(28)
+        );
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e----B-:0:0:1:52:0:0:0:5:10:N:0"),
+            XgidError::FieldCount(12)
+        );
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e----B-:0:0:1:52:0:0:0:5:10:0"),
+            XgidError::BadVariant("0".to_owned())
+        );
+        assert_eq!(
+            err("XGID=Ab----E-C---eE---c-e----B-:0:0:1:52:0:0:0:5:10"),
+            XgidError::BadChecker { pos: 0, found: 'A' }
+        );
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e----B-:0:0:1:52:0:0:2:5:10"),
+            XgidError::BadNumber {
+                field: 7,
+                value: "2".to_owned()
+            }
+        );
+        assert_eq!(
+            err("XGID=-b----E-C---eE---c-e----B-:1:-1:1:D:0:0:0:5:10"),
+            XgidError::Inconsistent("double without access to the cube")
+        );
+    }
+
+    #[test]
+    fn legacy_black_to_play() {
+        let id = "XGID=-b----E-C---eE---c-e----B-:0:0:-1:00:0:0:0:5:10";
+        let b = Board::from_xg_id(id).unwrap();
+        let legacy = Board::from_xgid(&b.xgid()).unwrap();
+        assert_eq!(legacy.player, Some(Player::Black));
+        assert_eq!(legacy.xg_id(), id);
+    }
+}
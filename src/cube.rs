@@ -0,0 +1,439 @@
+// Cubeful equities from cubeless probabilities
+// Janowski's model: the cubeful equity is x times the equity with a fully live cube,
+// which is linear in the winning chance between the take and cash points, plus 1 - x
+// times the cubeless equity. For matches the cash and take points at every cube level
+// come from the match equities, working down from the highest useful cube. For money
+// the Jacoby rule counts gammons as single games while the cube is centered, and a
+// beaver turns a double the taker is glad to take into one at twice the value.
+use std::collections::HashMap;
+
+use crate::board::{Board, Match, Player};
+use crate::evaluator::{fetch_match_equities, Probabilities};
+
+// the cube as seen from the player on roll
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeState {
+    Centered,
+    Owned,
+    Unavailable,
+}
+impl CubeState {
+    pub fn opponent(self) -> CubeState {
+        match self {
+            CubeState::Centered => CubeState::Centered,
+            CubeState::Owned => CubeState::Unavailable,
+            CubeState::Unavailable => CubeState::Owned,
+        }
+    }
+    pub fn of(board: &Board) -> CubeState {
+        match board.cube.owner() {
+            None => CubeState::Centered,
+            Some(p) if p == board.on_roll() => CubeState::Owned,
+            Some(_) => CubeState::Unavailable,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeAction {
+    NoDouble,
+    DoubleTake,
+    DoublePass,
+    // better to play on for a gammon than to cash
+    TooGood,
+}
+
+// Equities of the player on roll: points for money, match winning chance in a match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubeDecision {
+    pub no_double: f64,
+    pub double_take: f64,
+    pub double_pass: f64,
+    pub action: CubeAction,
+}
+impl CubeDecision {
+    pub fn equity(&self) -> f64 {
+        match self.action {
+            CubeAction::NoDouble | CubeAction::TooGood => self.no_double,
+            CubeAction::DoubleTake => self.double_take,
+            CubeAction::DoublePass => self.double_pass,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Janowski {
+    // cube life, from 0 for a dead cube to 1 for a fully live one
+    pub x: f64,
+}
+impl Default for Janowski {
+    fn default() -> Self {
+        Janowski { x: 0.68 }
+    }
+}
+
+impl Janowski {
+    pub fn new(x: f64) -> Janowski {
+        assert!((0. ..=1.).contains(&x));
+        Janowski { x }
+    }
+
+    // the cube action of the player on roll, from White's cubeless probabilities
+    pub fn decision(&self, board: &Board, p: &Probabilities) -> CubeDecision {
+        let mut m = Model::new(self.x, board, p, &fetch_match_equities);
+        let v = board.cube.value();
+        let s = CubeState::of(board);
+        let no_double = m.equity(v, s);
+        let mut double_take = m.equity(2 * v, CubeState::Unavailable);
+        let game = &board.game;
+        if game.is_money() && game.beaver && board.cube.level + 2 <= board.cube.max_level {
+            double_take = double_take.min(m.equity(4 * v, CubeState::Unavailable));
+        }
+        let double_pass = m.cash(v);
+        let action = if !board.can_double() || s == CubeState::Unavailable {
+            CubeAction::NoDouble
+        } else if double_take >= double_pass {
+            if no_double > double_pass {
+                CubeAction::TooGood
+            } else {
+                CubeAction::DoublePass
+            }
+        } else if double_take > no_double {
+            CubeAction::DoubleTake
+        } else {
+            CubeAction::NoDouble
+        };
+        CubeDecision {
+            no_double,
+            double_take,
+            double_pass,
+            action,
+        }
+    }
+
+    // White's cubeful equity after the best cube action of the player on roll
+    pub fn equity(&self, board: &Board, p: &Probabilities) -> f64 {
+        let e = self.decision(board, p).equity();
+        match (board.on_roll(), board.game.is_money()) {
+            (Player::White, _) => e,
+            (Player::Black, true) => -e,
+            (Player::Black, false) => 1. - e,
+        }
+    }
+}
+
+// everything from the side of the player on roll
+pub(crate) struct Model<'a> {
+    x: f64,
+    // White's match winning chance at a score
+    met: &'a dyn Fn(&Match) -> f64,
+    game: Match,
+    player: Player,
+    p: Probabilities,
+    // the highest cube value
+    max_cube: usize,
+    // the take and cash points per cube value, fully live
+    points: HashMap<usize, (f64, f64)>,
+}
+
+impl<'a> Model<'a> {
+    fn new(x: f64, board: &Board, p: &Probabilities, met: &'a dyn Fn(&Match) -> f64) -> Model<'a> {
+        let player = board.on_roll();
+        let p = if player == Player::White {
+            *p
+        } else {
+            p.flip()
+        };
+        Model::with_game(x, board.game, player, p, 1 << board.cube.max_level, met)
+    }
+    // `p` from the side of `player`
+    pub(crate) fn with_game(
+        x: f64,
+        game: Match,
+        player: Player,
+        p: Probabilities,
+        max_cube: usize,
+        met: &'a dyn Fn(&Match) -> f64,
+    ) -> Model<'a> {
+        Model {
+            x,
+            met,
+            game,
+            player,
+            p,
+            max_cube,
+            points: HashMap::new(),
+        }
+    }
+
+    // the result of winning or losing `points`
+    fn result(&self, win: bool, points: usize) -> f64 {
+        if self.game.is_money() {
+            return if win { points as f64 } else { -(points as f64) };
+        }
+        let mut g = self.game;
+        let winner = if win {
+            self.player
+        } else {
+            self.player.opponent()
+        };
+        g.add_score(winner, points);
+        let e = (self.met)(&g);
+        if self.player == Player::White {
+            e
+        } else {
+            1. - e
+        }
+    }
+    fn cash(&self, v: usize) -> f64 {
+        self.result(true, v)
+    }
+    fn dropped(&self, v: usize) -> f64 {
+        self.result(false, v)
+    }
+    // the average result of the games won and lost, played out at cube `v`
+    fn outcomes(&self, v: usize, s: CubeState) -> (f64, f64) {
+        let p = &self.p;
+        let jacoby = self.game.is_money() && self.game.jacoby && s == CubeState::Centered;
+        let average = |win: bool, total: f64, gammon: f64, backgammon: f64| {
+            if total <= 0. || jacoby {
+                return self.result(win, v);
+            }
+            ((total - gammon) * self.result(win, v)
+                + (gammon - backgammon) * self.result(win, 2 * v)
+                + backgammon * self.result(win, 3 * v))
+                / total
+        };
+        (
+            average(true, p.win, p.win_gammon, p.win_backgammon),
+            average(false, 1. - p.win, p.lose_gammon, p.lose_backgammon),
+        )
+    }
+    fn dead(&self, v: usize, s: CubeState, win: f64) -> f64 {
+        let (w, l) = self.outcomes(v, s);
+        win * w + (1. - win) * l
+    }
+
+    // whether the owner of a cube at `v` can still gain by doubling
+    fn alive(&self, owner: Player, v: usize) -> bool {
+        if 2 * v > self.max_cube || self.game.crawford {
+            return false;
+        }
+        if self.game.is_money() {
+            return true;
+        }
+        let score = if owner == Player::White {
+            self.game.score.0
+        } else {
+            self.game.score.1
+        };
+        score + v < self.game.length
+    }
+
+    // the take point of the player on roll and the cash point, fully live
+    fn points(&mut self, v: usize) -> (f64, f64) {
+        if let Some(&tp) = self.points.get(&v) {
+            return tp;
+        }
+        let tp = if self.game.is_money() {
+            // after a double, so the cube is no longer centered
+            let (w, l) = self.outcomes(1, CubeState::Owned);
+            let (w, l) = (w, -l);
+            ((l - 0.5) / (w + l + 0.5), (l + 1.) / (w + l + 0.5))
+        } else {
+            // the opponent doubles and the player owns the cube, or the other way round
+            let drop = self.dropped(v);
+            let cash = self.cash(v);
+            let take = solve(|p| self.live(2 * v, CubeState::Owned, p) - drop);
+            let cash = solve(|p| self.live(2 * v, CubeState::Unavailable, p) - cash);
+            (take, cash)
+        };
+        self.points.insert(v, tp);
+        tp
+    }
+
+    // the fully live equity at winning chance `p`
+    fn live(&mut self, v: usize, s: CubeState, p: f64) -> f64 {
+        let opponent = self.player.opponent();
+        let mine = s != CubeState::Unavailable && self.alive(self.player, v);
+        let theirs = s != CubeState::Owned && self.alive(opponent, v);
+        if !mine && !theirs {
+            return self.dead(v, s, p);
+        }
+        let (tp, cp) = self.points(v);
+        let (w, l) = self.outcomes(v, s);
+        let (low, high) = match (theirs, mine) {
+            (true, true) => ((tp, self.dropped(v)), (cp, self.cash(v))),
+            (false, true) => ((0., l), (cp, self.cash(v))),
+            _ => ((tp, self.dropped(v)), (1., w)),
+        };
+        if p <= low.0 {
+            if theirs {
+                low.1
+            } else {
+                l
+            }
+        } else if p >= high.0 {
+            if mine {
+                high.1
+            } else {
+                w
+            }
+        } else {
+            low.1 + (high.1 - low.1) * (p - low.0) / (high.0 - low.0)
+        }
+    }
+
+    pub(crate) fn equity(&mut self, v: usize, s: CubeState) -> f64 {
+        let win = self.p.win;
+        self.x * self.live(v, s, win) + (1. - self.x) * self.dead(v, s, win)
+    }
+}
+
+// the root of an increasing function on [0, 1], or the nearer end
+fn solve<F: FnMut(f64) -> f64>(mut f: F) -> f64 {
+    let (mut lo, mut hi) = (0., 1.);
+    if f(lo) >= 0. {
+        return lo;
+    }
+    if f(hi) <= 0. {
+        return hi;
+    }
+    for _ in 0..50 {
+        let mid = (lo + hi) / 2.;
+        if f(mid) < 0. {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn probs(win: f64, win_gammon: f64) -> Probabilities {
+        Probabilities {
+            win,
+            win_gammon,
+            win_backgammon: 0.,
+            lose_gammon: 0.,
+            lose_backgammon: 0.,
+        }
+    }
+    fn money() -> Board {
+        let mut b = Board::new();
+        b.game = Match::money(false, false);
+        b
+    }
+
+    #[test]
+    fn dead_cube() {
+        let b = money();
+        let dead = Janowski::new(0.);
+        let d = dead.decision(&b, &probs(0.6, 0.));
+        assert!((d.no_double - 0.2).abs() < 1e-9);
+        assert!((d.double_take - 0.4).abs() < 1e-9);
+        assert_eq!(d.double_pass, 1.);
+        assert_eq!(d.action, CubeAction::DoubleTake);
+        assert_eq!(
+            dead.decision(&b, &probs(0.4, 0.)).action,
+            CubeAction::NoDouble
+        );
+        assert_eq!(
+            dead.decision(&b, &probs(0.8, 0.)).action,
+            CubeAction::DoublePass
+        );
+        // a cubeless equity of 1.4 is better than cashing
+        assert_eq!(
+            dead.decision(&b, &probs(0.9, 0.6)).action,
+            CubeAction::TooGood
+        );
+        // Black on roll
+        let mut b = b;
+        b.player = Some(Player::Black);
+        assert!((dead.equity(&b, &probs(0.4, 0.)) + 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn jacoby_and_beavers() {
+        let dead = Janowski::new(0.);
+        // the gammons only count once the cube is turned
+        let mut b = money();
+        b.game.jacoby = true;
+        let d = dead.decision(&b, &probs(0.9, 0.6));
+        assert!((d.no_double - 0.8).abs() < 1e-9);
+        assert!((d.double_take - 2.8).abs() < 1e-9);
+        assert_eq!(d.action, CubeAction::DoublePass);
+        b.cube.position = Some(Player::White);
+        b.cube.level = 1;
+        let d = dead.decision(&b, &probs(0.9, 0.6));
+        assert!((d.no_double - 2.8).abs() < 1e-9);
+
+        // a bad double is beavered
+        let mut b = money();
+        b.game.beaver = true;
+        let d = dead.decision(&b, &probs(0.4, 0.));
+        assert!((d.double_take + 0.8).abs() < 1e-9);
+        assert_eq!(d.action, CubeAction::NoDouble);
+        let d = dead.decision(&b, &probs(0.6, 0.));
+        assert!((d.double_take - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn live_cube() {
+        let b = money();
+        let live = Janowski::new(1.);
+        // the take point is 20% and the cash point 80%
+        let d = live.decision(&b, &probs(0.5, 0.));
+        assert!(d.no_double.abs() < 1e-9);
+        let d = live.decision(&b, &probs(0.7, 0.));
+        assert!((d.no_double - 2. / 3.).abs() < 1e-9);
+        assert!((d.double_take - 0.5).abs() < 1e-9);
+        assert_eq!(d.action, CubeAction::NoDouble);
+        assert_eq!(
+            live.decision(&b, &probs(0.81, 0.)).action,
+            CubeAction::DoublePass
+        );
+
+        // owning the cube is worth something
+        let j = Janowski::default();
+        let mut owned = money();
+        owned.cube.position = Some(Player::White);
+        let mut theirs = money();
+        theirs.cube.position = Some(Player::Black);
+        let p = probs(0.6, 0.1);
+        let (o, c, u) = (
+            j.equity(&owned, &p),
+            j.equity(&b, &p),
+            j.equity(&theirs, &p),
+        );
+        assert!(o >= c && c > u, "{} {} {}", o, c, u);
+        assert_eq!(j.decision(&theirs, &p).action, CubeAction::NoDouble);
+    }
+
+    #[test]
+    fn match_play() {
+        let mut b = Board::new();
+        b.game = Match::with_length(3);
+        let j = Janowski::default();
+        let d = j.decision(&b, &probs(0.9, 0.));
+        assert_eq!(d.action, CubeAction::DoublePass);
+        let mut won = b.game;
+        won.add_score(Player::White, 1);
+        assert_eq!(d.double_pass, fetch_match_equities(&won));
+        let d = j.decision(&b, &probs(0.5, 0.));
+        assert_eq!(d.action, CubeAction::NoDouble);
+        assert!(d.no_double > 0.4 && d.no_double < 0.6);
+
+        // no cube in the Crawford game
+        b.game.score = (2, 0);
+        b.game.crawford = true;
+        let p = probs(0.3, 0.1);
+        let d = j.decision(&b, &p);
+        assert_eq!(d.action, CubeAction::NoDouble);
+        assert!((d.no_double - p.equity(&b.game, 1)).abs() < 1e-9);
+    }
+}
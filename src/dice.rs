@@ -0,0 +1,109 @@
+use crate::board::Dice;
+
+pub trait DiceSource {
+    fn roll(&mut self) -> Dice;
+    // White's die and Black's die for the opening, rerolled on doubles
+    fn init_roll(&mut self) -> Dice {
+        loop {
+            let d = self.roll();
+            if d.0 != d.1 {
+                return d;
+            }
+        }
+    }
+}
+
+// xorshift64*, reproducible from the seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // splitmix64 spreads nearby seeds apart
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // the state must not be zero
+        Rng(if z == 0 { 1 } else { z })
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+    // uniform in [0, 1)
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+    // uniform in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.unit() * n as f64) as usize
+    }
+    pub fn die(&mut self) -> usize {
+        self.below(6) + 1
+    }
+}
+impl DiceSource for Rng {
+    fn roll(&mut self) -> Dice {
+        Dice(self.die(), self.die())
+    }
+}
+
+// replays a fixed list of rolls, for tests
+#[derive(Debug, Clone)]
+pub struct Scripted {
+    dice: Vec<Dice>,
+    next: usize,
+}
+impl Scripted {
+    pub fn new(dice: Vec<Dice>) -> Scripted {
+        Scripted { dice, next: 0 }
+    }
+    pub fn remaining(&self) -> usize {
+        self.dice.len() - self.next
+    }
+}
+impl DiceSource for Scripted {
+    fn roll(&mut self) -> Dice {
+        let d = *self.dice.get(self.next).expect("scripted dice exhausted");
+        self.next += 1;
+        d
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seeded() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let xs: Vec<Dice> = (0..20).map(|_| a.roll()).collect();
+        let ys: Vec<Dice> = (0..20).map(|_| b.roll()).collect();
+        let zs: Vec<Dice> = (0..20).map(|_| c.roll()).collect();
+        assert_eq!(xs, ys);
+        assert_ne!(xs, zs);
+
+        let mut count = [0; 6];
+        for _ in 0..60000 {
+            count[a.die() - 1] += 1;
+        }
+        assert!(
+            count.iter().all(|&c| (9500..10500).contains(&c)),
+            "{:?}",
+            count
+        );
+        assert_ne!(Rng::new(0).next_u64(), 0);
+    }
+
+    #[test]
+    fn scripted() {
+        let mut s = Scripted::new(vec![Dice(3, 3), Dice(3, 1), Dice(6, 6)]);
+        assert_eq!(s.init_roll(), Dice(3, 1));
+        assert_eq!(s.remaining(), 1);
+        assert_eq!(s.roll(), Dice(6, 6));
+    }
+}
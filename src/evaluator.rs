@@ -1,18 +1,131 @@
 use std::collections::HashMap;
 
-use crate::board::{Action, Board, Dice, DiceRoll, Match, Player, State};
+use serde::Serialize;
 
-#[derive(Clone, Debug)]
-struct Equities {
-    actions: Vec<(Action, f64)>,
-    equity: f64,
+use crate::board::{Action, Board, DiceRoll, Match, Player, State};
+use crate::met;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Equities {
+    /// White's equity after each action
+    pub actions: Vec<(Action, f64)>,
+    /// White's match winning chance, or points per game for money
+    pub equity: f64,
+    /// how the game ends with the best play, when the evaluator knows
+    pub outcomes: Option<Probabilities>,
 }
 impl Equities {
-    fn new(actions: Vec<(Action, f64)>, equity: f64) -> Self {
-        Self { actions, equity }
+    pub fn new(actions: Vec<(Action, f64)>, equity: f64) -> Self {
+        Self {
+            actions,
+            equity,
+            outcomes: None,
+        }
+    }
+    pub fn with_outcomes(self, outcomes: Option<Probabilities>) -> Self {
+        Self { outcomes, ..self }
     }
+    pub fn emg(&self, board: &Board) -> f64 {
+        emg(board, self.equity)
+    }
+}
+// Cubeless outcome probabilities from White's side; gammons include backgammons.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Probabilities {
+    pub win: f64,
+    pub win_gammon: f64,
+    pub win_backgammon: f64,
+    pub lose_gammon: f64,
+    pub lose_backgammon: f64,
 }
-fn max_eq(eq: &[(Action, f64)], player: Player) -> (Action, f64) {
+impl Probabilities {
+    // even chances without gammons, for a position an evaluator knows nothing about
+    pub const EVEN: Probabilities = Probabilities {
+        win: 0.5,
+        win_gammon: 0.,
+        win_backgammon: 0.,
+        lose_gammon: 0.,
+        lose_backgammon: 0.,
+    };
+    // the certain outcome of a game won by `player` with `points` per cube value
+    pub fn outcome(player: Player, points: usize) -> Probabilities {
+        let won = [points >= 1, points >= 2, points >= 3].map(|w| w as u8 as f64);
+        let p = Probabilities {
+            win: won[0],
+            win_gammon: won[1],
+            win_backgammon: won[2],
+            lose_gammon: 0.,
+            lose_backgammon: 0.,
+        };
+        if player == Player::White {
+            p
+        } else {
+            p.flip()
+        }
+    }
+    // the largest change of any chance
+    fn distance(&self, other: &Probabilities) -> f64 {
+        [
+            self.win - other.win,
+            self.win_gammon - other.win_gammon,
+            self.win_backgammon - other.win_backgammon,
+            self.lose_gammon - other.lose_gammon,
+            self.lose_backgammon - other.lose_backgammon,
+        ]
+        .iter()
+        .fold(0., |d, x| d.max(x.abs()))
+    }
+    pub fn flip(&self) -> Probabilities {
+        Probabilities {
+            win: 1. - self.win,
+            win_gammon: self.lose_gammon,
+            win_backgammon: self.lose_backgammon,
+            lose_gammon: self.win_gammon,
+            lose_backgammon: self.win_backgammon,
+        }
+    }
+    // White's match winning chance when the game is played out with the cube at `cube`,
+    // or White's points per game for money
+    pub fn equity(&self, game: &Match, cube: usize) -> f64 {
+        let outcomes = [
+            (Player::White, 1, self.win - self.win_gammon),
+            (Player::White, 2, self.win_gammon - self.win_backgammon),
+            (Player::White, 3, self.win_backgammon),
+            (Player::Black, 1, 1. - self.win - self.lose_gammon),
+            (Player::Black, 2, self.lose_gammon - self.lose_backgammon),
+            (Player::Black, 3, self.lose_backgammon),
+        ];
+        outcomes
+            .iter()
+            .map(|&(p, points, prob)| {
+                if game.is_money() {
+                    let sign = if p == Player::White { 1. } else { -1. };
+                    return prob * sign * (points * cube) as f64;
+                }
+                let mut g = *game;
+                g.add_score(p, points * cube);
+                prob * fetch_match_equities(&g)
+            })
+            .sum()
+    }
+}
+
+// each action with its equity from `after`
+pub(crate) fn after_each<I, F>(actions: I, mut after: F) -> Vec<(Action, f64)>
+where
+    I: IntoIterator<Item = Action>,
+    F: FnMut(&Action) -> f64,
+{
+    actions
+        .into_iter()
+        .map(|a| {
+            let e = after(&a);
+            (a, e)
+        })
+        .collect()
+}
+
+pub(crate) fn max_eq(eq: &[(Action, f64)], player: Player) -> (Action, f64) {
     eq.iter()
         .max_by(|(_, a), (_, b)| {
             if player == Player::White {
@@ -25,102 +138,171 @@ fn max_eq(eq: &[(Action, f64)], player: Player) -> (Action, f64) {
         .clone()
 }
 
-trait Evaluator {
+pub trait Evaluator {
     fn eval(&mut self, board: &Board) -> Equities;
 }
 
-struct OpenEvaluator {
+// Solves the game tree exactly. Positions that can recur before the game ends (after a
+// hit) form strongly connected components, found as in Tarjan's algorithm; each one is
+// solved by iteration, starting from the equity at the current score, to a tolerance.
+pub struct OpenEvaluator {
     tree: HashMap<Board, Equities>,
+    open: HashMap<Board, Open>,
+    stack: Vec<Board>,
+    next: usize,
+    // the lowest index of an open position reached from the one being expanded
+    low: usize,
+    tolerance: f64,
+    // components left at the last sweep short of the tolerance
+    unconverged: usize,
+}
+
+// a position whose evaluation is in progress
+struct Open {
+    index: usize,
+    estimate: Equities,
 }
 
 impl Evaluator for OpenEvaluator {
     fn eval(&mut self, board: &Board) -> Equities {
-        if let Some(eq) = self.tree.get(&board) {
+        if let Some(eq) = self.tree.get(board) {
             return eq.clone();
         }
-        let eq = match board.state() {
-            State::Init => self.eval_init(&board),
-            State::ToDouble => self.eval_to_double(&board),
-            State::ToRoll => self.eval_to_roll(&board),
-            State::Doubled => self.eval_doubled(&board),
-            State::ToMove => self.eval_move(&board),
-            State::End => self.eval_end(&board),
-            State::MatchEnd => self.eval_end(board),
-        };
-        self.tree.insert(board.to_owned(), eq.clone());
-        eq
+        if let Some(o) = self.open.get(board) {
+            self.low = self.low.min(o.index);
+            return o.estimate.clone();
+        }
+        let index = self.next;
+        self.next += 1;
+        let guess =
+            Equities::new(vec![], terminal_equity(board)).with_outcomes(Some(Probabilities::EVEN));
+        self.open.insert(
+            board.to_owned(),
+            Open {
+                index,
+                estimate: guess,
+            },
+        );
+        self.stack.push(board.to_owned());
+        let outer = std::mem::replace(&mut self.low, index);
+        let eq = self.expand(board);
+        let low = std::mem::replace(&mut self.low, outer);
+        if low < index {
+            self.low = self.low.min(low);
+            self.open.get_mut(board).unwrap().estimate = eq.clone();
+            return eq;
+        }
+
+        let at = self.stack.iter().rposition(|b| b == board).unwrap();
+        let component = self.stack.split_off(at);
+        self.open.get_mut(board).unwrap().estimate = eq;
+        // every action changes the position, so a single position cannot recur alone
+        if component.len() > 1 {
+            self.solve(&component);
+        }
+        for b in component {
+            let o = self.open.remove(&b).unwrap();
+            self.tree.insert(b, o.estimate);
+        }
+        self.tree[board].clone()
+    }
+}
+
+impl Default for OpenEvaluator {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl OpenEvaluator {
-    fn new() -> Self {
+    pub const TOLERANCE: f64 = 1e-9;
+    // sweeps over a component before giving up on reaching the tolerance
+    pub const MAX_SWEEPS: usize = 1_000;
+
+    pub fn new() -> Self {
+        Self::with_tolerance(Self::TOLERANCE)
+    }
+    pub fn with_tolerance(tolerance: f64) -> Self {
         Self {
             tree: HashMap::new(),
+            open: HashMap::new(),
+            stack: Vec::new(),
+            next: 0,
+            low: usize::MAX,
+            tolerance,
+            unconverged: 0,
         }
     }
-    fn eval_init(&mut self, board: &Board) -> Equities {
-        let p = fetch_match_equities(&board.game);
-        Equities::new(vec![], p)
+    /// The number of recurring components given up on after `MAX_SWEEPS` sweeps, whose
+    /// equities are the last estimates rather than within the tolerance.
+    pub fn unconverged(&self) -> usize {
+        self.unconverged
     }
-    fn eval_end(&mut self, board: &Board) -> Equities {
-        let p = fetch_match_equities(&board.game);
-        Equities::new(vec![(Action::Reset, p)], p)
+    fn expand(&mut self, board: &Board) -> Equities {
+        let next = successors(board);
+        let eqs: Vec<_> = next.iter().map(|(_, b)| self.eval(b)).collect();
+        combine(board, &next, &eqs.iter().collect::<Vec<_>>())
     }
-    fn eval_move(&mut self, board: &Board) -> Equities {
-        let moves = board.actions();
-        let mut equities = Vec::new();
-        for mov in moves {
-            let mut next = board.clone();
-            next.act(&mov);
-            equities.push((mov, self.eval(&next).equity));
-        }
-        let e = max_eq(&equities, board.player.unwrap()).1;
-        Equities::new(equities, e)
-    }
-    fn eval_to_double(&mut self, board: &Board) -> Equities {
-        let mut no_double = board.clone();
-        no_double.act(&Action::NoDouble);
-        let no_double_eq = self.eval(&no_double).equity;
-        let mut eq = vec![(Action::NoDouble, no_double_eq)];
-        if !board.can_double() {
-            return Equities::new(eq, no_double_eq);
-        }
-        let mut double = board.clone();
-        double.act(&Action::Double);
-        let double_eq = self.eval(&double).equity;
-        eq.push((Action::Double, double_eq));
-        let e = max_eq(&eq, board.player.unwrap()).1;
-        Equities::new(eq, e)
-    }
-    fn eval_to_roll(&mut self, board: &Board) -> Equities {
-        let mut roll_eq = 0.;
-        let roll = DiceRoll::all()
-            .into_iter()
-            .map(|dice| {
-                let act = Action::Roll(dice);
-                let mut roll = board.clone();
-                roll.act(&act);
-                let eq = self.eval(&roll).equity;
-                roll_eq += dice.prob() * eq;
-                (act, eq)
+    // Gauss-Seidel sweeps over the positions of a component; every position reached
+    // from it is either in it or already solved
+    fn solve(&mut self, component: &[Board]) {
+        let index: HashMap<&Board, usize> =
+            component.iter().enumerate().map(|(i, b)| (b, i)).collect();
+        let next: Vec<_> = component.iter().map(successors).collect();
+        let children: Vec<Vec<Child>> = next
+            .iter()
+            .map(|next| {
+                next.iter()
+                    .map(|(_, b)| match index.get(b) {
+                        Some(&i) => Child::Open(i),
+                        None => Child::Solved(self.tree[b].clone()),
+                    })
+                    .collect()
             })
             .collect();
-        Equities::new(roll, roll_eq)
+        let mut values: Vec<_> = component
+            .iter()
+            .map(|b| self.open[b].estimate.clone())
+            .collect();
+        for sweep in 1.. {
+            let mut delta: f64 = 0.;
+            for i in (0..component.len()).rev() {
+                let eqs: Vec<_> = children[i]
+                    .iter()
+                    .map(|c| match c {
+                        Child::Open(j) => &values[*j],
+                        Child::Solved(eq) => eq,
+                    })
+                    .collect();
+                let eq = combine(&component[i], &next[i], &eqs);
+                delta = delta.max((eq.equity - values[i].equity).abs());
+                // the outcomes too, which a known equity may still hide
+                let moved = match (&eq.outcomes, &values[i].outcomes) {
+                    (Some(a), Some(b)) => a.distance(b),
+                    (None, None) => 0.,
+                    _ => 1.,
+                };
+                delta = delta.max(moved);
+                values[i] = eq;
+            }
+            if delta < self.tolerance {
+                break;
+            }
+            if sweep == Self::MAX_SWEEPS {
+                self.unconverged += 1;
+                break;
+            }
+        }
+        for (b, eq) in component.iter().zip(values) {
+            self.open.get_mut(b).unwrap().estimate = eq;
+        }
     }
-    fn eval_doubled(&mut self, board: &Board) -> Equities {
-        let mut pass = board.clone();
-        pass.act(&Action::Pass);
-        let pass_eq = self.eval(&pass).equity;
 
-        let mut take = board.clone();
-        take.act(&Action::Take);
-        let take_eq = self.eval(&take).equity;
-        let e = vec![(Action::Pass, pass_eq), (Action::Take, take_eq)];
-        let ea = max_eq(&e, board.player.unwrap()).1;
-        Equities::new(e, ea)
+    pub fn gen_tree(&self, board: &Board) -> Tree {
+        self.subtree(board, &mut Vec::new())
     }
-
-    fn gen_tree(&self, board: &Board) -> Tree {
+    // a position recurring on its own path is a leaf
+    fn subtree(&self, board: &Board, path: &mut Vec<Board>) -> Tree {
         if board.state() == State::Init
             || board.state() == State::End
             || board.state() == State::MatchEnd
@@ -128,17 +310,26 @@ impl OpenEvaluator {
             return Tree {
                 root: board.clone(),
                 children: vec![],
-                equity: fetch_match_equities(&board.game),
+                equity: terminal_equity(board),
             };
         }
         let p = self.tree.get(board).unwrap();
+        if path.contains(board) {
+            return Tree {
+                root: board.clone(),
+                children: vec![],
+                equity: p.equity,
+            };
+        }
+        path.push(board.clone());
         let mut res = vec![];
-        for (act, e) in &p.actions {
+        for (act, _) in &p.actions {
             let mut b = board.clone();
             b.act(act);
-            let t = self.gen_tree(&b);
+            let t = self.subtree(&b, path);
             res.push((act.clone(), t));
         }
+        path.pop();
         Tree {
             root: board.clone(),
             children: res,
@@ -148,13 +339,13 @@ impl OpenEvaluator {
 }
 
 #[derive(Clone, Debug, PartialEq)]
-struct Tree {
-    root: Board,
-    children: Vec<(Action, Tree)>,
-    equity: f64,
+pub struct Tree {
+    pub root: Board,
+    pub children: Vec<(Action, Tree)>,
+    pub equity: f64,
 }
 impl Tree {
-    fn display(&self, depth: usize, max: usize) {
+    pub fn display(&self, depth: usize, max: usize) {
         println!("{}{} {}", " ".repeat(depth), self.root.xgid(), self.equity);
         if depth >= max {
             return;
@@ -166,33 +357,142 @@ impl Tree {
     }
 }
 
-fn fetch_match_equities(game: &Match) -> f64 {
-    if let Some(winner) = game.winner() {
-        if winner == Player::White {
-            1.
-        } else {
-            -1.
+enum Child {
+    Open(usize),
+    Solved(Equities),
+}
+
+// White's equities in a position from those after each action
+fn combine(board: &Board, next: &[(Action, Board)], eqs: &[&Equities]) -> Equities {
+    match board.state() {
+        State::Init => Equities::new(vec![], terminal_equity(board)),
+        State::End | State::MatchEnd => {
+            let p = terminal_equity(board);
+            Equities::new(vec![(Action::Reset, p)], p).with_outcomes(terminal_outcomes(board))
         }
-    } else {
-        assert!(game.length <= 5, "assert match length {} <= 5", game.length);
-        // https://bkgm.com/articles/Kazaross/RockwellKazarossMET/index.html
-        let pc = vec![0.50, 0.51, 0.68, 0.69, 0.81];
-        let table = vec![
-            vec![0.50, 0.68, 0.75, 0.81, 0.84],
-            vec![0.32, 0.50, 0.57, 0.63, 0.66],
-            vec![0.25, 0.43, 0.50, 0.56, 0.59],
-            vec![0.19, 0.37, 0.44, 0.50, 0.53],
-            vec![0.16, 0.34, 0.41, 0.47, 0.50],
-        ];
-        let (w, b) = game.score;
-        let (aw, ab) = (game.length - w, game.length - b);
-        if aw == 1 && !game.crawford {
-            return pc[ab - 1];
+        State::ToRoll => {
+            let mut roll_eq = 0.;
+            let mut outcomes = Vec::new();
+            let mut roll = Vec::new();
+            for ((act, _), eq) in next.iter().zip(eqs) {
+                let Action::Roll(dice) = act else {
+                    unreachable!()
+                };
+                roll_eq += dice.prob() * eq.equity;
+                outcomes.push((dice.prob(), eq.outcomes));
+                roll.push((act.clone(), eq.equity));
+            }
+            Equities::new(roll, roll_eq).with_outcomes(mix(&outcomes))
         }
-        if ab == 1 && !game.crawford {
-            return 1. - pc[aw - 1];
+        // the equities after each action, with the outcomes of the best one
+        State::ToDouble | State::Doubled | State::ToMove | State::ToChoose => {
+            let equities: Vec<_> = next
+                .iter()
+                .zip(eqs)
+                .map(|((act, _), eq)| (act.clone(), eq.equity))
+                .collect();
+            let (best, e) = max_eq(&equities, board.player.unwrap());
+            let i = equities.iter().position(|(a, _)| *a == best).unwrap();
+            Equities::new(equities, e).with_outcomes(eqs[i].outcomes)
         }
-        table[aw - 1][ab - 1]
+    }
+}
+
+// the positions after each action the evaluator considers
+fn successors(board: &Board) -> Vec<(Action, Board)> {
+    let actions = match board.state() {
+        State::ToDouble if board.can_double() => vec![Action::NoDouble, Action::Double],
+        State::ToDouble => vec![Action::NoDouble],
+        State::ToRoll => DiceRoll::all().into_iter().map(Action::Roll).collect(),
+        State::Doubled | State::ToMove | State::ToChoose => board.actions(),
+        State::Init | State::End | State::MatchEnd => vec![],
+    };
+    actions
+        .into_iter()
+        .map(|act| {
+            let mut next = board.clone();
+            next.act(&act);
+            (act, next)
+        })
+        .collect()
+}
+
+// the weighted average of the outcomes, if all of them are known
+fn mix(parts: &[(f64, Option<Probabilities>)]) -> Option<Probabilities> {
+    let mut sum = [0.; 5];
+    for (w, p) in parts {
+        let p = (*p)?;
+        let values = [
+            p.win,
+            p.win_gammon,
+            p.win_backgammon,
+            p.lose_gammon,
+            p.lose_backgammon,
+        ];
+        for (s, v) in sum.iter_mut().zip(values) {
+            *s += w * v;
+        }
+    }
+    Some(Probabilities {
+        win: sum[0],
+        win_gammon: sum[1],
+        win_backgammon: sum[2],
+        lose_gammon: sum[3],
+        lose_backgammon: sum[4],
+    })
+}
+
+// White's equity in money-equivalent units (EMG) per cube value. A finished game is
+// worth the points won; in a match the winning chance is mapped linearly so that
+// losing and winning a single game at the current cube are -1 and +1.
+pub fn emg(board: &Board, equity: f64) -> f64 {
+    let cube = board.cube.value();
+    if let Some(r) = board.result {
+        let points = (r.score / cube) as f64;
+        return if r.player == Player::White {
+            points
+        } else {
+            -points
+        };
+    }
+    if board.game.is_money() {
+        return equity / cube as f64;
+    }
+    let single = |p: Player| {
+        let mut g = board.game;
+        g.add_score(p, cube);
+        fetch_match_equities(&g)
+    };
+    let (win, lose) = (single(Player::White), single(Player::Black));
+    2. * (equity - lose) / (win - lose) - 1.
+}
+
+// White's equity once the game is over: match winning chance, or points won for money
+pub fn terminal_equity(board: &Board) -> f64 {
+    if !board.game.is_money() {
+        return fetch_match_equities(&board.game);
+    }
+    match board.result {
+        Some(r) if r.player == Player::White => r.score as f64,
+        Some(r) => -(r.score as f64),
+        None => 0.,
+    }
+}
+
+// how the finished game ended, per cube value
+pub fn terminal_outcomes(board: &Board) -> Option<Probabilities> {
+    board
+        .result
+        .map(|r| Probabilities::outcome(r.player, r.score / board.cube.value()))
+}
+
+// White's match winning chance, from the current match equity table; 0 once Black has
+// won the match
+pub fn fetch_match_equities(game: &Match) -> f64 {
+    match game.winner() {
+        Some(Player::White) => 1.,
+        Some(Player::Black) => 0.,
+        None => met::covering(game.length).equity(game),
     }
 }
 
@@ -200,10 +500,11 @@ fn fetch_match_equities(game: &Match) -> f64 {
 mod test {
 
     use super::*;
+    use crate::hyper::Hypergammon;
 
     #[test]
     fn minimum_case() {
-        let b = Board::from_xgid("XGID=-A----------------------a-:0:0:1::0:0:0:1:10");
+        let b = Board::from_xgid("XGID=-A----------------------a-:0:0:1::0:0:0:1:10").unwrap();
         let mut e = OpenEvaluator::new();
         let eq = e.eval(&b);
         println!("{:?}", eq);
@@ -215,7 +516,7 @@ mod test {
     }
     #[test]
     fn small_case() {
-        let b = Board::from_xgid("XGID=-----A-----------------a--:0:0:1::0:0:0:3:10");
+        let b = Board::from_xgid("XGID=-----A-----------------a--:0:0:1::0:0:0:3:10").unwrap();
         println!("{}", b);
         let mut e = OpenEvaluator::new();
         let eq = e.eval(&b);
@@ -228,7 +529,7 @@ mod test {
     }
     #[test]
     fn take_case() {
-        let mut b = Board::from_xgid("XGID=-----------------A-----a--:0:0:1::0:0:0:3:10");
+        let b = Board::from_xgid("XGID=-----------------A-----a--:0:0:1::0:0:0:3:10").unwrap();
         println!("{}", b);
         let mut e = OpenEvaluator::new();
         let eq = e.eval(&b);
@@ -241,25 +542,139 @@ mod test {
         // e.gen_tree(&b).display(0, 5);
     }
 
+    #[test]
+    fn match_won() {
+        // a match won by Black is a winning chance of 0 like any other, not -1
+        let mut game = Match::with_length(3);
+        game.add_score(Player::Black, 3);
+        assert_eq!(fetch_match_equities(&game), 0.);
+        let mut game = Match::with_length(3);
+        game.add_score(Player::White, 3);
+        assert_eq!(fetch_match_equities(&game), 1.);
+    }
+
+    #[test]
+    fn gammons() {
+        // White bears off the last checker with Black's checkers all at home
+        let ends = [
+            ("XGID=-A-----------------o------:0:0:1::0:0:0:5:10", 2),
+            ("XGID=-A---a-------------n------:0:0:1::0:0:0:5:10", 3),
+        ];
+        for (xgid, points) in ends {
+            let b = Board::from_xgid(xgid).unwrap();
+            let eq = OpenEvaluator::new().eval(&b);
+            let mut game = b.game;
+            game.add_score(Player::White, points);
+            assert!((eq.equity - fetch_match_equities(&game)).abs() < 1e-9);
+            let p = eq.outcomes.unwrap();
+            assert!((p.win_gammon - 1.).abs() < 1e-9);
+            assert!((p.win_backgammon - (points == 3) as u8 as f64).abs() < 1e-9);
+            assert!(eq.emg(&b) > 1.);
+        }
+
+        // White rolls rather than cashing the game with a double
+        let mut b = Board::from_xgid("XGID=-----A-----------------a--:0:0:1::0:0:0:3:10").unwrap();
+        let mut e = OpenEvaluator::new();
+        assert_eq!(e.eval(&b).outcomes.unwrap().win, 1.);
+        b.act(&Action::NoDouble);
+        let eq = e.eval(&b);
+        let p = eq.outcomes.unwrap();
+        assert!(p.win > 0.5 && p.win < 1.);
+        assert_eq!(p.win_gammon, 0.);
+        let e = eq.emg(&b);
+        assert!(e > 0. && e < 1., "{}", e);
+    }
+
+    #[test]
+    fn money() {
+        // a gammon for White, cashed with a double only under the Jacoby rule
+        let xgid = "XGID=-A-----------------o------:0:0:1::0:0:0:0:10";
+        let mut b = Board::from_xgid(xgid).unwrap();
+        assert!((OpenEvaluator::new().eval(&b).equity - 2.).abs() < 1e-9);
+        b.game.jacoby = true;
+        let eq = OpenEvaluator::new().eval(&b);
+        assert!((eq.equity - 1.).abs() < 1e-9);
+        assert!((eq.emg(&b) - 1.).abs() < 1e-9);
+
+        // a bad double for White is beavered
+        let mut b = Board::from_xgid("XGID=------------A-----------a-:0:0:1::0:0:0:0:10").unwrap();
+        b.game = Match::money(false, true);
+        let mut e = OpenEvaluator::new();
+        let eq = e.eval(&b);
+        assert_eq!(max_eq(&eq.actions, Player::White).0, Action::NoDouble);
+        b.act(&Action::Double);
+        let eq = e.eval(&b);
+        assert_eq!(eq.actions.len(), 3);
+        assert_eq!(max_eq(&eq.actions, Player::Black).0, Action::Beaver);
+        // White wins at once with 33, 44, 55 or 66
+        let want = 4. * (4. / 36. - 32. / 36.);
+        assert!((eq.equity - want).abs() < 1e-9, "{}", eq.equity);
+    }
+
+    #[test]
+    fn contact() {
+        // the checkers can hit each other, so positions recur
+        let xgid = "XGID=---a----A-----------------:0:0:1::0:0:0:0:0:H1";
+        let b = Board::from_xgid(xgid).unwrap();
+        let mut e = OpenEvaluator::new();
+        let eq = e.eval(&b);
+        let (table, _) = Hypergammon::solve(1, 1e-6);
+        let want = table.cubeless(&[8], &[22]).unwrap();
+        let p = eq.outcomes.unwrap();
+        assert!((p.win - want.win).abs() < 1e-5, "{:?} {:?}", p, want);
+        assert!(
+            (p.win_gammon - want.win_gammon).abs() < 1e-5,
+            "{:?} {:?}",
+            p,
+            want
+        );
+        let money = want.equity(&b.game, 1);
+        assert!((eq.equity - money).abs() < 1e-5, "{} {}", eq.equity, money);
+    }
+
+    #[test]
+    fn no_convergence() {
+        // no change is ever below a tolerance of 0, so the last estimate is kept
+        let xgid = "XGID=---a----A-------------------:0:0:1::0:0:0:0:0:H1";
+        let b = Board::from_xgid(xgid).unwrap();
+        let mut e = OpenEvaluator::with_tolerance(0.);
+        let eq = e.eval(&b);
+        assert!(e.unconverged() > 0);
+        assert!(eq.equity.is_finite());
+        let exact = OpenEvaluator::new().eval(&b).equity;
+        assert!((eq.equity - exact).abs() < 1e-6, "{} {}", eq.equity, exact);
+    }
+
     #[test]
     fn match_eq() {
         let p = fetch_match_equities(&Match {
             length: 5,
             score: (0, 0),
             crawford: false,
+            jacoby: false,
+            beaver: false,
         });
-        assert!((p - 0.5) < 1e-5, "{}", p);
+        assert!((p - 0.5).abs() < 1e-5, "{}", p);
         let p = fetch_match_equities(&Match {
             length: 5,
             score: (4, 1),
             crawford: true,
+            jacoby: false,
+            beaver: false,
         });
-        assert!((p - 0.81) < 1e-5, "{}", p);
+        assert!((p - 0.81).abs() < 1e-5, "{}", p);
         let p = fetch_match_equities(&Match {
             length: 5,
             score: (4, 1),
             crawford: false,
+            jacoby: false,
+            beaver: false,
         });
-        assert!((p - 0.69) < 1e-5, "{}", p);
+        assert!((p - 0.69).abs() < 1e-5, "{}", p);
+        // longer than the default table
+        let mut game = Match::with_length(31);
+        assert!((fetch_match_equities(&game) - 0.5).abs() < 1e-9);
+        game.add_score(Player::White, 30);
+        assert!(fetch_match_equities(&game) > 0.9);
     }
 }
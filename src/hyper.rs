@@ -0,0 +1,801 @@
+// Exact solution of Hypergammon
+// With at most three checkers a side every position fits in a table, solved by value
+// iteration since positions can recur after a hit. Each side is the multiset of its
+// checkers' spots in its own numbering: 0 when borne off, the points, and 25 on the bar.
+// Cubeful values are for money without the Jacoby rule or beavers.
+use std::io::{self, Read, Write};
+use std::iter::repeat_n;
+use std::path::Path;
+
+use crate::board::{Action, Board, Dice, DiceRoll, Pieces, Player, State, Variant};
+use crate::cube::CubeState;
+use crate::evaluator::{
+    after_each, max_eq, terminal_equity, terminal_outcomes, Equities, Evaluator, Probabilities,
+};
+
+const MAGIC: &[u8; 4] = b"BGH1";
+const SPOTS: usize = Pieces::BAR + 1;
+const STATES: [CubeState; 3] = [
+    CubeState::Centered,
+    CubeState::Owned,
+    CubeState::Unavailable,
+];
+
+// where the values of each cube state are stored
+fn slot(s: CubeState) -> usize {
+    match s {
+        CubeState::Centered => 0,
+        CubeState::Owned => 1,
+        CubeState::Unavailable => 2,
+    }
+}
+
+const fn binomial(n: usize, k: usize) -> usize {
+    let mut c = 1;
+    let mut i = 0;
+    while i < k {
+        c = c * (n - i) / (i + 1);
+        i += 1;
+    }
+    c
+}
+// the term of side_index for each spot and rank, looked up as moves are made
+const RANKS: [[usize; 3]; SPOTS] = {
+    let mut r = [[0; 3]; SPOTS];
+    let mut s = 0;
+    while s < SPOTS {
+        let mut i = 0;
+        while i < 3 {
+            r[s][i] = binomial(s + i, i + 1);
+            i += 1;
+        }
+        s += 1;
+    }
+    r
+};
+// the number of ways to place n checkers
+fn side_count(n: usize) -> usize {
+    binomial(SPOTS + n - 1, n)
+}
+// rank of sorted spots among all multisets of the same size
+fn side_index(spots: &[usize]) -> usize {
+    spots.iter().enumerate().map(|(i, &s)| RANKS[s][i]).sum()
+}
+// all sides with n checkers, by index
+fn all_sides(n: usize) -> Vec<Vec<usize>> {
+    let mut sides = vec![vec![]];
+    for _ in 0..n {
+        sides = sides
+            .into_iter()
+            .flat_map(|s: Vec<usize>| {
+                let from = s.last().copied().unwrap_or(0);
+                (from..SPOTS).map(move |x| {
+                    let mut s = s.clone();
+                    s.push(x);
+                    s
+                })
+            })
+            .collect();
+    }
+    sides.sort_by_key(|s| side_index(s));
+    sides
+}
+
+fn side_of(pieces: &Pieces, p: Player) -> Vec<usize> {
+    let ps = pieces.reversed(p);
+    let mut spots = vec![];
+    for i in 0..=Pieces::BAR {
+        if let Some((o, c)) = ps.get(i) {
+            if o == p {
+                spots.extend(repeat_n(i, c));
+            }
+        }
+    }
+    spots
+}
+
+// whether a point holds checkers of both sides
+fn shares_point(mine: &[usize], theirs: &[usize]) -> bool {
+    mine.iter()
+        .any(|&s| (1..Pieces::BAR).contains(&s) && theirs.contains(&(Pieces::BAR - s)))
+}
+
+// What a move leads to: the next position from the opponent's side, or WON plus the
+// points when the game is over. Ten million positions fit well below WON.
+const WON: u32 = u32::MAX - 3;
+// the children of every position and roll are kept between sweeps up to two checkers
+const CACHE_LIMIT: usize = 1 << 22;
+
+// the 5 probabilities of the player on roll seen from the other side
+fn flip(p: [f32; 5]) -> [f32; 5] {
+    [1. - p[0], p[3], p[4], p[1], p[2]]
+}
+fn cubeless_equity(p: &[f32; 5]) -> f32 {
+    2. * p[0] - 1. + p[1] - p[3] + p[2] - p[4]
+}
+// money equity with the cube action of the player on roll, per cube value
+fn cubeful(v: &[f32; 3], s: CubeState) -> f32 {
+    let no_double = v[slot(s)];
+    if s == CubeState::Unavailable {
+        return no_double;
+    }
+    let double = (2. * v[slot(CubeState::Unavailable)]).min(1.);
+    no_double.max(double)
+}
+
+// one side's checkers, sorted, in the first n entries
+type Side = [usize; 3];
+
+// Makes the moves on the spots of each side rather than through Board, as a sweep
+// over three checkers plays some 170 million rolls.
+struct Mover {
+    checkers: usize,
+    size: usize,
+    // the ways to play so far, each once: both sides and the pips of a single step
+    ways: Vec<(Side, Side, usize)>,
+    next: Vec<(Side, Side, usize)>,
+    // where the ways that cannot go on end, by the steps played
+    ends: Vec<(usize, Side, Side, usize)>,
+    children: Vec<u32>,
+}
+impl Mover {
+    fn new(checkers: usize) -> Mover {
+        Mover {
+            checkers,
+            size: side_count(checkers),
+            ways: vec![],
+            next: vec![],
+            ends: vec![],
+            children: vec![],
+        }
+    }
+
+    // each way for `mine` to play the dice in order against `theirs`, by the same rules
+    // as Backgammon: the bar first, bearing off from home with the back checker allowed
+    // to overshoot, and stopping early once no die can be played
+    fn play(&mut self, mine: Side, theirs: Side, dice: &[usize]) {
+        let n = self.checkers;
+        self.ways.clear();
+        self.ways.push((mine, theirs, 0));
+        for (steps, &d) in dice.iter().enumerate() {
+            self.next.clear();
+            for &(mine, theirs, first) in &self.ways {
+                let backman = mine[n - 1];
+                let on_bar = backman == Pieces::BAR;
+                let before = self.next.len();
+                for i in (0..n).rev() {
+                    let from = mine[i];
+                    if backman == 0
+                        || i + 1 < n && mine[i + 1] == from
+                        || on_bar && from != Pieces::BAR
+                    {
+                        continue;
+                    }
+                    let step = if from == backman && from <= Pieces::INNER_BOARD {
+                        d.min(from)
+                    } else {
+                        d
+                    };
+                    if from < step || backman > Pieces::INNER_BOARD && from == step {
+                        continue;
+                    }
+                    let to = from - step;
+                    let hit = (1..Pieces::BAR).contains(&to).then(|| Pieces::BAR - to);
+                    let blots = hit.map_or(0, |h| theirs[..n].iter().filter(|&&t| t == h).count());
+                    if blots > 1 {
+                        continue;
+                    }
+                    let mut m = mine;
+                    m[i] = to;
+                    // back in order, the moved checker having gone down
+                    let mut k = i;
+                    while k > 0 && m[k - 1] > m[k] {
+                        m.swap(k - 1, k);
+                        k -= 1;
+                    }
+                    let mut t = theirs;
+                    if let (Some(h), 1) = (hit, blots) {
+                        let mut k = t[..n].iter().position(|&x| x == h).unwrap();
+                        t[k] = Pieces::BAR;
+                        while k + 1 < n && t[k + 1] < t[k] {
+                            t.swap(k, k + 1);
+                            k += 1;
+                        }
+                    }
+                    // the first step only counts when it is the whole move
+                    let way = (m, t, if steps == 0 { from - to } else { 0 });
+                    if !self.next.contains(&way) {
+                        self.next.push(way);
+                    }
+                }
+                if self.next.len() == before {
+                    self.ends.push((steps, mine, theirs, first));
+                }
+            }
+            std::mem::swap(&mut self.ways, &mut self.next);
+        }
+        for &(m, t, first) in &self.ways {
+            self.ends.push((dice.len(), m, t, first));
+        }
+    }
+
+    // the distinct children of the moves of the player on roll
+    fn children(&mut self, mine: &[usize], theirs: &[usize], d: Dice) -> &[u32] {
+        let n = self.checkers;
+        let mut side = [0; 3];
+        side[..n].copy_from_slice(mine);
+        let mut other = [0; 3];
+        other[..n].copy_from_slice(theirs);
+        self.ends.clear();
+        if d.0 == d.1 {
+            self.play(side, other, &[d.0; 4]);
+        } else {
+            self.play(side, other, &[d.0, d.1]);
+            self.play(side, other, &[d.1, d.0]);
+        }
+        // as many dice as possible, and the larger move when only one can be played
+        let steps = self.ends.iter().map(|e| e.0).max().unwrap();
+        let pips = self
+            .ends
+            .iter()
+            .filter(|e| e.0 == steps)
+            .map(|e| e.3)
+            .max()
+            .unwrap();
+        self.children.clear();
+        for &(s, m, t, first) in &self.ends {
+            if s != steps || steps == 1 && first != pips {
+                continue;
+            }
+            self.children.push(if m[..n].iter().all(|&x| x == 0) {
+                WON + points(&t[..n]) as u32
+            } else {
+                (side_index(&t[..n]) * self.size + side_index(&m[..n])) as u32
+            });
+        }
+        self.children.sort_unstable();
+        self.children.dedup();
+        &self.children
+    }
+}
+
+// what the side that has borne off all its checkers wins against `theirs`
+fn points(theirs: &[usize]) -> usize {
+    if theirs.contains(&0) {
+        1
+    } else if theirs
+        .iter()
+        .any(|&s| s > Pieces::BAR - 1 - Pieces::INNER_BOARD)
+    {
+        3
+    } else {
+        2
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hypergammon {
+    checkers: usize,
+    size: usize,
+    // the player on roll's chances to win, win a gammon and a backgammon, and lose
+    // a gammon and a backgammon, without the cube
+    probabilities: Vec<[f32; 5]>,
+    // no double with the cube centered, owned and unavailable
+    no_double: Vec<[f32; 3]>,
+}
+
+impl Hypergammon {
+    // sweeps over all positions before giving up on reaching the tolerance
+    pub const MAX_SWEEPS: usize = 200;
+
+    // Sweeps over all positions until no equity moves by more than `tolerance`, which
+    // f32 values of up to 3 points cannot resolve below 1e-6. Two checkers take half
+    // a minute; three take some 8 million positions, half a gigabyte, and about 50
+    // sweeps of two and a half minutes each on one core. Returns the table with the
+    // largest change of the last sweep, which is still above the tolerance only when
+    // MAX_SWEEPS ran out.
+    pub fn solve(checkers: usize, tolerance: f32) -> (Hypergammon, f32) {
+        assert!((1..=3).contains(&checkers));
+        assert!(tolerance >= 1e-6);
+        let sides = all_sides(checkers);
+        let size = sides.len();
+        debug_assert_eq!(size, side_count(checkers));
+        let rolls = DiceRoll::all_with_prob();
+        let mut table = Hypergammon {
+            checkers,
+            size,
+            probabilities: vec![[0.; 5]; size * size],
+            no_double: vec![[0.; 3]; size * size],
+        };
+        // positions still to play; a side with everything off has already won
+        let mut live: Vec<(usize, usize)> = (0..size)
+            .flat_map(|x| (0..size).map(move |y| (x, y)))
+            .filter(|&(x, y)| sides[x].iter().any(|&s| s > 0) && sides[y].iter().any(|&s| s > 0))
+            .filter(|&(x, y)| !shares_point(&sides[x], &sides[y]))
+            .collect();
+        // fewer pips first, so that a race follows from values already swept
+        live.sort_by_key(|&(x, y)| (sides[x].iter().chain(&sides[y]).sum::<usize>(), y));
+        let mut mover = Mover::new(checkers);
+        let cache = (live.len() * rolls.len() <= CACHE_LIMIT).then(|| {
+            let mut flat = vec![];
+            let mut offsets = vec![0];
+            for &(x, y) in &live {
+                for &(d, _) in &rolls {
+                    flat.extend_from_slice(mover.children(&sides[x], &sides[y], d));
+                    offsets.push(flat.len());
+                }
+            }
+            (flat, offsets)
+        });
+        let mut delta = f32::INFINITY;
+        for _ in 0..Self::MAX_SWEEPS {
+            delta = 0.;
+            for (n, &(x, y)) in live.iter().enumerate() {
+                let mut p = [0.; 5];
+                let mut v = [0.; 3];
+                for (r, &(d, prob)) in rolls.iter().enumerate() {
+                    let k = n * rolls.len() + r;
+                    let list = match &cache {
+                        Some((flat, offsets)) => &flat[offsets[k]..offsets[k + 1]],
+                        None => mover.children(&sides[x], &sides[y], d),
+                    };
+                    let prob = prob as f32;
+                    // the move that is best without the cube
+                    let mut best = (f32::MIN, [0.; 5]);
+                    let mut cube = [f32::MIN; 3];
+                    for &c in list {
+                        let (after, values) = if c >= WON {
+                            let points = (c - WON) as usize;
+                            let w = [points >= 2, points >= 3].map(|w| w as u8 as f32);
+                            ([1., w[0], w[1], 0., 0.], [points as f32; 3])
+                        } else {
+                            let i = c as usize;
+                            let nd = &table.no_double[i];
+                            (
+                                flip(table.probabilities[i]),
+                                STATES.map(|s| -cubeful(nd, s.opponent())),
+                            )
+                        };
+                        let e = cubeless_equity(&after);
+                        if e > best.0 {
+                            best = (e, after);
+                        }
+                        for (c, v) in cube.iter_mut().zip(values) {
+                            *c = c.max(v);
+                        }
+                    }
+                    for (p, b) in p.iter_mut().zip(best.1) {
+                        *p += prob * b;
+                    }
+                    for (v, c) in v.iter_mut().zip(cube) {
+                        *v += prob * c;
+                    }
+                }
+                let i = x * size + y;
+                // the equities, as a tie between moves may swap the gammons every sweep
+                let before = cubeless_equity(&table.probabilities[i]);
+                delta = delta.max((before - cubeless_equity(&p)).abs());
+                for (a, b) in table.no_double[i].iter().zip(&v) {
+                    delta = delta.max((a - b).abs());
+                }
+                table.probabilities[i] = p;
+                table.no_double[i] = v;
+            }
+            if delta < tolerance {
+                break;
+            }
+        }
+        (table, delta)
+    }
+
+    pub fn checkers(&self) -> usize {
+        self.checkers
+    }
+
+    // the spots of each side, in any order
+    fn index(&self, on_roll: &[usize], opponent: &[usize]) -> Option<usize> {
+        let side = |s: &[usize]| {
+            let mut s = s.to_vec();
+            s.sort_unstable();
+            (s.len() == self.checkers && s.iter().all(|&x| x < SPOTS)).then(|| side_index(&s))
+        };
+        Some(side(on_roll)? * self.size + side(opponent)?)
+    }
+    // the chances of the player on roll without the cube
+    pub fn cubeless(&self, on_roll: &[usize], opponent: &[usize]) -> Option<Probabilities> {
+        let p = self.probabilities[self.index(on_roll, opponent)?];
+        Some(Probabilities {
+            win: p[0] as f64,
+            win_gammon: p[1] as f64,
+            win_backgammon: p[2] as f64,
+            lose_gammon: p[3] as f64,
+            lose_backgammon: p[4] as f64,
+        })
+    }
+    // money equity of the player on roll per cube value, when not doubling now
+    pub fn no_double(&self, on_roll: &[usize], opponent: &[usize], s: CubeState) -> Option<f64> {
+        let v = self.no_double[self.index(on_roll, opponent)?];
+        Some(v[slot(s)] as f64)
+    }
+    // with the best cube action of the player on roll
+    pub fn cubeful(&self, on_roll: &[usize], opponent: &[usize], s: CubeState) -> Option<f64> {
+        let v = self.no_double[self.index(on_roll, opponent)?];
+        Some(cubeful(&v, s) as f64)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[self.checkers as u8])?;
+        for (p, v) in self.probabilities.iter().zip(&self.no_double) {
+            for x in p.iter().chain(v) {
+                w.write_all(&x.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Hypergammon> {
+        Hypergammon::read_from(std::fs::File::open(path)?)
+    }
+    pub fn read_from<R: Read>(mut r: R) -> io::Result<Hypergammon> {
+        let invalid = |s: &str| io::Error::new(io::ErrorKind::InvalidData, s.to_owned());
+        let mut bytes = vec![];
+        r.read_to_end(&mut bytes)?;
+        if bytes.len() < 5 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a hypergammon table"));
+        }
+        let checkers = bytes[4] as usize;
+        if !(1..=3).contains(&checkers) {
+            return Err(invalid("bad hypergammon table header"));
+        }
+        let size = side_count(checkers);
+        if bytes.len() != 5 + size * size * 8 * 4 {
+            return Err(invalid("bad hypergammon table size"));
+        }
+        let mut probabilities = Vec::with_capacity(size * size);
+        let mut no_double = Vec::with_capacity(size * size);
+        for b in bytes[5..].chunks(32) {
+            let mut x = [0.; 8];
+            for (x, c) in x.iter_mut().zip(b.chunks(4)) {
+                *x = f32::from_le_bytes([c[0], c[1], c[2], c[3]]);
+            }
+            probabilities.push([x[0], x[1], x[2], x[3], x[4]]);
+            no_double.push([x[5], x[6], x[7]]);
+        }
+        Ok(Hypergammon {
+            checkers,
+            size,
+            probabilities,
+            no_double,
+        })
+    }
+}
+
+// Exact evaluator for Hypergammon with the table's number of checkers. Money games use
+// the cubeful equities, matches the cubeless chances. It panics on other variants.
+pub struct HypergammonEvaluator {
+    pub table: Hypergammon,
+}
+impl HypergammonEvaluator {
+    pub fn new(table: Hypergammon) -> HypergammonEvaluator {
+        HypergammonEvaluator { table }
+    }
+
+    fn sides(&self, board: &Board) -> (Vec<usize>, Vec<usize>) {
+        assert_eq!(board.variant, Variant::Hypergammon(self.table.checkers));
+        let x = board.on_roll();
+        (
+            side_of(&board.pieces, x),
+            side_of(&board.pieces, x.opponent()),
+        )
+    }
+
+    // before the roll of the player on roll
+    pub fn probabilities(&self, board: &Board) -> Option<Probabilities> {
+        if !matches!(
+            board.state(),
+            State::ToDouble | State::ToRoll | State::Doubled
+        ) {
+            return None;
+        }
+        let (x, y) = self.sides(board);
+        let p = self.table.cubeless(&x, &y)?;
+        Some(if board.on_roll() == Player::White {
+            p
+        } else {
+            p.flip()
+        })
+    }
+
+    // White's equity of the player on roll's equity `e` per cube value
+    fn white(&self, board: &Board, e: f64) -> f64 {
+        let sign = if board.on_roll() == Player::White {
+            1.
+        } else {
+            -1.
+        };
+        sign * e * board.cube.value() as f64
+    }
+    // before the roll, with the best cube action when `double` is set
+    fn lookup(&self, board: &Board, double: bool) -> f64 {
+        if !board.game.is_money() {
+            return self
+                .probabilities(board)
+                .unwrap()
+                .equity(&board.game, board.cube.value());
+        }
+        let (x, y) = self.sides(board);
+        let s = CubeState::of(board);
+        let e = if double {
+            self.table.cubeful(&x, &y, s)
+        } else {
+            self.table.no_double(&x, &y, s)
+        };
+        self.white(board, e.unwrap())
+    }
+
+    // White's equity of the position by the table, without rolling on
+    fn value(&self, board: &Board) -> f64 {
+        match board.state() {
+            State::Init | State::End | State::MatchEnd => terminal_equity(board),
+            State::ToDouble => self.lookup(board, board.can_double()),
+            State::ToRoll => self.lookup(board, false),
+            State::Doubled | State::ToMove | State::ToChoose => {
+                let eq = after_each(board.actions(), |a| self.after(board, a));
+                max_eq(&eq, board.player.unwrap()).1
+            }
+        }
+    }
+    fn after(&self, board: &Board, act: &Action) -> f64 {
+        let mut next = board.clone();
+        next.act(act);
+        self.value(&next)
+    }
+}
+
+impl Evaluator for HypergammonEvaluator {
+    fn eval(&mut self, board: &Board) -> Equities {
+        match board.state() {
+            State::Init | State::MatchEnd => Equities::new(vec![], terminal_equity(board)),
+            State::End => {
+                let e = terminal_equity(board);
+                Equities::new(vec![(Action::Reset, e)], e).with_outcomes(terminal_outcomes(board))
+            }
+            State::ToDouble if board.game.is_money() => {
+                let eq = after_each(board.actions(), |a| self.after(board, a));
+                let e = max_eq(&eq, board.player.unwrap()).1;
+                Equities::new(eq, e).with_outcomes(self.probabilities(board))
+            }
+            State::ToDouble => {
+                let e = self.value(board);
+                Equities::new(vec![(Action::NoDouble, e)], e)
+                    .with_outcomes(self.probabilities(board))
+            }
+            State::ToRoll => {
+                let rolls = DiceRoll::all().into_iter().map(Action::Roll);
+                Equities::new(
+                    after_each(rolls, |a| self.after(board, a)),
+                    self.value(board),
+                )
+                .with_outcomes(self.probabilities(board))
+            }
+            State::Doubled | State::ToMove | State::ToChoose => {
+                let eq = after_each(board.actions(), |a| self.after(board, a));
+                let e = max_eq(&eq, board.player.unwrap()).1;
+                Equities::new(eq, e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bearoff::{BearoffEvaluator, OneSided};
+    use crate::board::Match;
+
+    // the children of White's moves with the roll, by the rules engine
+    fn by_board(n: usize, mine: &[usize], theirs: &[usize], d: Dice, size: usize) -> Vec<u32> {
+        let mut pieces = Pieces::empty();
+        for (p, spots) in [(Player::White, mine), (Player::Black, theirs)] {
+            for &s in spots {
+                let i = match (p, s) {
+                    (Player::White, s) => s,
+                    (Player::Black, 0) => Pieces::BLACK_GOAL,
+                    (Player::Black, Pieces::BAR) => Pieces::BLACK_BAR,
+                    (Player::Black, s) => Pieces::BAR - s,
+                };
+                let c = pieces.get(i).map_or(0, |(_, c)| c);
+                pieces.set(i, p, c + 1);
+            }
+        }
+        let board = Board {
+            pieces,
+            dice: DiceRoll::roll(d.0, d.1),
+            player: Some(Player::White),
+            game: Match::money(false, false),
+            ..Board::with_variant(Variant::Hypergammon(n))
+        };
+        let mut res: Vec<u32> = board
+            .moves()
+            .into_iter()
+            .map(|m| {
+                let mut b = board.clone();
+                b.act(&Action::Move(m));
+                match b.result {
+                    Some(r) => WON + r.score as u32,
+                    None => {
+                        let x = side_index(&side_of(&b.pieces, Player::Black));
+                        let y = side_index(&side_of(&b.pieces, Player::White));
+                        (x * size + y) as u32
+                    }
+                }
+            })
+            .collect();
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+
+    #[test]
+    fn children_follow_the_rules() {
+        for n in 1..=3 {
+            let sides = all_sides(n);
+            let size = sides.len();
+            let mut mover = Mover::new(n);
+            // every position of one checker, and a spread of the others
+            let step = [1, 61, 4999][n - 1];
+            for k in (0..size * size).step_by(step) {
+                let (mine, theirs) = (&sides[k / size], &sides[k % size]);
+                let over = [mine, theirs].iter().any(|s| s.iter().all(|&x| x == 0));
+                if over || shares_point(mine, theirs) {
+                    continue;
+                }
+                for d in DiceRoll::all() {
+                    let expected = by_board(n, mine, theirs, d, size);
+                    let children = mover.children(mine, theirs, d);
+                    assert_eq!(children, expected, "{:?} {:?} {:?}", mine, theirs, d);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sides() {
+        for n in 1..=3 {
+            let sides = all_sides(n);
+            assert_eq!(sides.len(), side_count(n));
+            assert!(sides.iter().enumerate().all(|(i, s)| side_index(s) == i));
+        }
+        assert_eq!(side_count(3), 3276);
+        let b = Board::with_variant(Variant::Hypergammon(3));
+        assert_eq!(side_of(&b.pieces, Player::Black), vec![22, 23, 24]);
+    }
+
+    #[test]
+    fn one_checker() {
+        let (table, delta) = Hypergammon::solve(1, 1e-6);
+        assert!(delta < 1e-6);
+        // a race: the winner always wins a gammon
+        let b = Board::from_xgid("XGID=-----A----------------a---:0:0:1::0:0:0:0:10:H1").unwrap();
+        let p = table.cubeless(&[5], &[3]).unwrap();
+        let race = BearoffEvaluator::new(OneSided::generate(6, 1));
+        let q = race.probabilities(&b).unwrap();
+        assert!((p.win - q.win).abs() < 1e-5, "{:?} {:?}", p, q);
+        assert!((p.win_gammon - p.win).abs() < 1e-5);
+        assert_eq!(p.win_backgammon, 0.);
+
+        // the player on roll is the favourite at the start
+        let start = table.cubeless(&[24], &[24]).unwrap();
+        assert!(start.win > 0.5 && start.win < 1.);
+        let c = CubeState::Centered;
+        let e = table.cubeful(&[24], &[24], c).unwrap();
+        assert!(e >= table.no_double(&[24], &[24], c).unwrap());
+        assert!(table.cubeful(&[24], &[24], CubeState::Owned).unwrap() >= e - 1e-5);
+        assert_eq!(table.cubeless(&[24, 23], &[24]), None);
+    }
+
+    #[test]
+    #[ignore = "solves some 120 thousand positions, half a minute unoptimized"]
+    fn two_checkers() {
+        let (table, _) = Hypergammon::solve(2, 1e-5);
+        // a race agrees with the bearoff database, gammons included
+        let b = Board::from_xgid("XGID=---A-A---------------a-a----:0:0:1::0:0:0:0:10:H2").unwrap();
+        let p = table.cubeless(&[5, 3], &[4, 2]).unwrap();
+        let q = BearoffEvaluator::new(OneSided::generate(6, 2))
+            .probabilities(&b)
+            .unwrap();
+        assert!((p.win - q.win).abs() < 1e-4, "{:?} {:?}", p, q);
+        assert!(
+            (p.win_gammon - q.win_gammon).abs() < 1e-4,
+            "{:?} {:?}",
+            p,
+            q
+        );
+
+        // every position with contact agrees with the average over the rolls
+        let mut e = HypergammonEvaluator::new(table);
+        let mut b = Board::with_variant(Variant::Hypergammon(2));
+        b.game = Match::money(false, false);
+        b.act(&Action::InitRoll(Dice(4, 2)));
+        b.act(&b.actions()[0].clone());
+        b.act(&Action::NoDouble);
+        let eq = e.eval(&b);
+        let mean: f64 = eq
+            .actions
+            .iter()
+            .map(|(a, v)| match a {
+                Action::Roll(d) => d.prob() * v,
+                _ => unreachable!(),
+            })
+            .sum();
+        assert!((mean - eq.equity).abs() < 1e-4, "{} {}", mean, eq.equity);
+        let p = e.probabilities(&b).unwrap();
+        assert!(p.win > 0. && p.win < 1. && p.win_gammon > 0.);
+    }
+
+    #[test]
+    #[ignore = "solves some 8 million positions, about an hour and a half"]
+    fn three_checkers() {
+        let (table, _) = Hypergammon::solve(3, 1e-5);
+        // a race agrees with the bearoff database
+        let b = Board::from_xgid("XGID=--AA-A---------------a-aa---:0:0:1::0:0:0:0:10:H3").unwrap();
+        let p = table.cubeless(&[5, 3, 2], &[4, 2, 1]).unwrap();
+        let q = BearoffEvaluator::new(OneSided::generate(6, 3))
+            .probabilities(&b)
+            .unwrap();
+        assert!((p.win - q.win).abs() < 1e-4, "{:?} {:?}", p, q);
+
+        // the player on roll is the favourite at the start, and may double
+        let start = [24, 23, 22];
+        assert!(table.cubeless(&start, &start).unwrap().win > 0.5);
+        let c = CubeState::Centered;
+        let e = table.cubeful(&start, &start, c).unwrap();
+        assert!(e >= table.no_double(&start, &start, c).unwrap());
+    }
+
+    #[test]
+    fn evaluator() {
+        let (table, _) = Hypergammon::solve(1, 1e-6);
+        let mut bytes = vec![];
+        table.write_to(&mut bytes).unwrap();
+        assert_eq!(Hypergammon::read_from(&bytes[..]).unwrap(), table);
+
+        let mut e = HypergammonEvaluator::new(table);
+        let mut b = Board::with_variant(Variant::Hypergammon(1));
+        b.game = Match::money(false, false);
+        b.act(&Action::InitRoll(Dice(3, 1)));
+        let eq = e.eval(&b);
+        assert_eq!(eq.actions.len(), b.actions().len());
+        let (best, v) = max_eq(&eq.actions, Player::White);
+        b.act(&best);
+        // Black is on roll before the cube decision
+        let after = e.eval(&b);
+        assert!((after.equity - v).abs() < 1e-9);
+        let p = after.outcomes.unwrap();
+        assert!(p.win > 0. && p.win < 1.);
+
+        let mut m = b.clone();
+        m.game = Match::with_length(5);
+        let eq = e.eval(&m);
+        assert_eq!(eq.actions, vec![(Action::NoDouble, eq.equity)]);
+        assert!((eq.equity - p.equity(&m.game, 1)).abs() < 1e-9);
+
+        // the table agrees with itself one roll later
+        b.act(&Action::NoDouble);
+        let eq = e.eval(&b);
+        let mean: f64 = eq
+            .actions
+            .iter()
+            .map(|(a, v)| match a {
+                Action::Roll(d) => d.prob() * v,
+                _ => unreachable!(),
+            })
+            .sum();
+        assert!((mean - eq.equity).abs() < 1e-5, "{} {}", mean, eq.equity);
+    }
+}
@@ -1,2 +1,29 @@
-mod board;
-mod evaluator;
+//! Backgammon rules engine and match equity evaluator.
+//!
+//! ```
+//! use backgammon_wasm::board::{Action, Board, Dice, Move, Player};
+//!
+//! let mut board = Board::new();
+//! board.act(&Action::InitRoll(Dice(3, 1)));
+//! let mov = Move::parse(&board, "8/5 6/5").unwrap();
+//! assert!(board.moves().contains(&mov));
+//! board.act(&Action::Move(mov));
+//! assert_eq!(board.pieces.point(Player::White, 5), 2);
+//! assert_eq!(board.pieces.bar(Player::Black), 0);
+//! assert_eq!(board.cube.value(), 1);
+//! assert_eq!(board.score(), (0, 0));
+//! ```
+pub mod agent;
+pub mod bearoff;
+pub mod board;
+pub mod cube;
+pub mod dice;
+pub mod evaluator;
+pub mod hyper;
+pub mod met;
+pub mod neural;
+pub mod play;
+pub mod rollout;
+pub mod search;
+pub mod train;
+mod wasm;
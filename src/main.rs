@@ -1 +1,237 @@
-fn main() {}
+use std::io::{self, BufRead, Write};
+
+use backgammon_wasm::agent::Agent;
+use backgammon_wasm::board::{Action, Board, Move, Player, State, Transcript};
+use backgammon_wasm::dice::{DiceSource, Rng};
+use backgammon_wasm::hyper::Hypergammon;
+use backgammon_wasm::neural::Network;
+use backgammon_wasm::train::{Benchmark, TrainConfig, Trainer};
+
+// the system clock is not there on wasm32, so the library only takes seeds
+fn seeded_by_time() -> Rng {
+    let t = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    Rng::new(t)
+}
+
+fn read_line(prompt: &str) -> String {
+    print!("{}", prompt);
+    io::stdout().flush().unwrap();
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line).unwrap() == 0 {
+        std::process::exit(0);
+    }
+    line.trim().to_owned()
+}
+
+fn ask(prompt: &str, yes: &str, no: &str) -> bool {
+    loop {
+        let line = read_line(prompt).to_lowercase();
+        if line.starts_with(yes) {
+            return true;
+        }
+        if line.starts_with(no) {
+            return false;
+        }
+    }
+}
+
+struct Human;
+impl Agent for Human {
+    fn double(&mut self, _: &Board) -> bool {
+        ask("Double? (y/n) ", "y", "n")
+    }
+    fn take(&mut self, board: &Board) -> bool {
+        println!("{}", board);
+        ask("Take or pass? (t/p) ", "t", "p")
+    }
+    fn play(&mut self, board: &Board, moves: &[Move]) -> Move {
+        for (i, m) in moves.iter().enumerate() {
+            println!("{:>3}: {}", i + 1, m.to_str());
+        }
+        loop {
+            let line = read_line("Your move: ");
+            if let Ok(i) = line.parse::<usize>() {
+                if 1 <= i && i <= moves.len() {
+                    return moves[i - 1].clone();
+                }
+            }
+            match Move::parse(board, &line) {
+                Ok(m) => return m,
+                Err(e) => println!("Illegal move: {}", e),
+            }
+        }
+    }
+}
+
+// race and safety heuristic
+struct Computer;
+impl Agent for Computer {
+    fn double(&mut self, _: &Board) -> bool {
+        false
+    }
+    fn take(&mut self, _: &Board) -> bool {
+        true
+    }
+    fn play(&mut self, board: &Board, moves: &[Move]) -> Move {
+        let p = board.player.unwrap();
+        moves
+            .iter()
+            .max_by_key(|m| {
+                let mut b = board.clone();
+                b.act(&Action::Move((*m).clone()));
+                let ps = &b.pieces;
+                let race = ps.pips(p.opponent()) as isize - ps.pips(p) as isize;
+                race * 4 - ps.blots(p) as isize * 5
+            })
+            .unwrap()
+            .clone()
+    }
+}
+
+fn play(length: usize, white: &mut dyn Agent, black: &mut dyn Agent) -> Transcript {
+    let mut rng = seeded_by_time();
+    let mut transcript = Transcript::new(length, "White", "Black");
+    let mut board = transcript.start();
+    loop {
+        let state = board.state();
+        let action = match state {
+            State::Init => {
+                let dice = rng.init_roll();
+                println!("White rolls {}, Black rolls {}", dice.0, dice.1);
+                Action::InitRoll(dice)
+            }
+            State::ToRoll => Action::Roll(rng.roll()),
+            State::ToDouble | State::Doubled | State::ToMove | State::ToChoose => {
+                let player = board.player.unwrap();
+                if state == State::ToMove {
+                    println!("{}", board);
+                }
+                let action = match player {
+                    Player::White => white.act(&board),
+                    Player::Black => black.act(&board),
+                };
+                match &action {
+                    Action::Move(m) => println!("{:?} plays {}", player, m.to_str()),
+                    Action::NoDouble => {}
+                    a => println!("{:?} {:?}", player, a),
+                }
+                action
+            }
+            State::End => {
+                println!("{}", board);
+                Action::Reset
+            }
+            State::MatchEnd => {
+                println!("{}", board);
+                println!("{:?} wins the match", board.game.winner().unwrap());
+                break;
+            }
+        };
+        board.act(&action);
+        transcript.actions.push(action);
+    }
+    transcript
+}
+
+// trains the weights in `path` by self-play, starting from random weights if missing
+fn train(path: &str, epochs: usize, benchmark: Option<&String>) -> Result<(), String> {
+    let benchmark = match benchmark {
+        Some(b) => {
+            let text = std::fs::read_to_string(b).map_err(|e| format!("{}: {}", b, e))?;
+            Benchmark::parse(&text).map_err(|e| format!("{}: {}", b, e))?
+        }
+        None => Benchmark::default(),
+    };
+    let mut rng = seeded_by_time();
+    let net = match Network::load(path) {
+        Ok(net) => net,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Network::random(40, &mut rng),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    let config = TrainConfig {
+        checkpoint: Some(path.into()),
+        ..TrainConfig::default()
+    };
+    let mut trainer = Trainer::new(net, config, rng);
+    for _ in 0..epochs {
+        let r = trainer
+            .epoch(&benchmark)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        println!(
+            "epoch {}: {} games, {:.1} moves per game, benchmark error {:.4}",
+            r.epoch, r.games, r.moves, r.error
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|s| s.as_str()) == Some("train") {
+        let epochs = args.get(3).map_or(Ok(10), |s| s.parse::<usize>());
+        let result = match (args.get(2), epochs) {
+            (Some(path), Ok(epochs)) => train(path, epochs, args.get(4)),
+            _ => Err(format!(
+                "usage: {} train <weights> [epochs] [benchmark]",
+                args[0]
+            )),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map(|s| s.as_str()) == Some("hyper") {
+        let checkers = args.get(3).map_or(Ok(3), |s| s.parse::<usize>());
+        // as fine as the f32 values go, for the exact table by default
+        let tolerance = args.get(4).map_or(Ok(1e-6), |s| s.parse::<f32>());
+        let result = match (args.get(2), checkers, tolerance) {
+            (Some(path), Ok(n), Ok(t)) if (1..=3).contains(&n) && t >= 1e-6 => {
+                let (table, delta) = Hypergammon::solve(n, t);
+                if delta >= t {
+                    eprintln!(
+                        "still moving by {} after {} sweeps",
+                        delta,
+                        Hypergammon::MAX_SWEEPS
+                    );
+                }
+                table.save(path).map_err(|e| format!("{}: {}", path, e))
+            }
+            _ => Err(format!(
+                "usage: {} hyper <table> [checkers] [tolerance, at least 1e-6]",
+                args[0]
+            )),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let length = match args.get(1).map(|s| s.parse::<usize>()) {
+        None => 1,
+        Some(Ok(l)) if l > 0 => l,
+        Some(_) => {
+            eprintln!(
+                "usage: {} [length] [human|computer] [transcript.mat]",
+                args[0]
+            );
+            std::process::exit(1);
+        }
+    };
+    let mut black: Box<dyn Agent> = match args.get(2).map(|s| s.as_str()) {
+        Some("human") => Box::new(Human),
+        _ => Box::new(Computer),
+    };
+    let transcript = play(length, &mut Human, black.as_mut());
+    if let Some(path) = args.get(3) {
+        if let Err(e) = std::fs::write(path, transcript.write()) {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
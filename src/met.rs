@@ -0,0 +1,441 @@
+// Match equity tables
+// Tables are generated by Zadeh's recursion, working up from the scores closest to
+// the end: every game starts even, is won as a gammon at the given rate, and the
+// cube is handled by the Janowski model of the cube module. After the Crawford game
+// the trailer doubles at once, and the leader's free drop at 2-away is worth a bit.
+// The default table is the published Rockwell-Kazaross table within 5 points, extended
+// by the recursion. Custom tables can be read from CSV or gnubg XML files.
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::board::{Match, Player};
+use crate::cube::{CubeState, Model};
+use crate::evaluator::Probabilities;
+
+#[derive(Debug)]
+pub enum MetError {
+    Io(io::Error),
+    // line number, starting at 1
+    BadNumber(usize, String),
+    // the pre-Crawford table must be square
+    NotSquare,
+    MissingPostCrawford,
+    Empty,
+}
+impl fmt::Display for MetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetError::Io(e) => write!(f, "{}", e),
+            MetError::BadNumber(n, s) => write!(f, "line {}: bad number {}", n, s),
+            MetError::NotSquare => write!(f, "the pre-Crawford table is not square"),
+            MetError::MissingPostCrawford => write!(f, "no post-Crawford table"),
+            MetError::Empty => write!(f, "no table"),
+        }
+    }
+}
+impl std::error::Error for MetError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetParams {
+    // the share of games won that are gammons
+    pub gammon_rate: f64,
+    // cube life in the Janowski model
+    pub cube_life: f64,
+    // what the free drop is worth to the leader at 1-away against 2-away
+    pub free_drop: f64,
+}
+impl Default for MetParams {
+    fn default() -> Self {
+        MetParams {
+            gammon_rate: 0.26,
+            cube_life: 0.8,
+            free_drop: 0.012,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Met {
+    // the chance of the player a-away against b-away at pre[a - 1][b - 1],
+    // where 1-away is the Crawford game
+    pre: Vec<Vec<f64>>,
+    // the chance of the trailer n-away after the Crawford game at post[n - 1]
+    post: Vec<f64>,
+}
+
+impl Met {
+    pub fn new(pre: Vec<Vec<f64>>, post: Vec<f64>) -> Result<Met, MetError> {
+        if pre.is_empty() {
+            return Err(MetError::Empty);
+        }
+        if pre.iter().any(|r| r.len() != pre.len()) {
+            return Err(MetError::NotSquare);
+        }
+        if post.len() < pre.len() {
+            return Err(MetError::MissingPostCrawford);
+        }
+        Ok(Met { pre, post })
+    }
+
+    // https://bkgm.com/articles/Kazaross/RockwellKazarossMET/index.html
+    pub fn rockwell_kazaross() -> Met {
+        Met {
+            pre: vec![
+                vec![0.50, 0.68, 0.75, 0.81, 0.84],
+                vec![0.32, 0.50, 0.57, 0.63, 0.66],
+                vec![0.25, 0.43, 0.50, 0.56, 0.59],
+                vec![0.19, 0.37, 0.44, 0.50, 0.53],
+                vec![0.16, 0.34, 0.41, 0.47, 0.50],
+            ],
+            post: vec![0.50, 0.49, 0.32, 0.31, 0.19],
+        }
+    }
+
+    pub fn generate(length: usize, params: &MetParams) -> Met {
+        Met::generate_from(None, length, params)
+    }
+    // this table, with the scores beyond it up to `length` generated
+    pub fn extend(&self, length: usize, params: &MetParams) -> Met {
+        if length <= self.length() {
+            return self.clone();
+        }
+        Met::generate_from(Some(self), length, params)
+    }
+    // generates the entries not in `base`
+    fn generate_from(base: Option<&Met>, length: usize, params: &MetParams) -> Met {
+        assert!(length > 0);
+        let covered = base.map_or(0, |m| m.length());
+        let g = params.gammon_rate;
+        let post_at = |post: &[f64], n: isize| if n <= 0 { 1. } else { post[n as usize - 1] };
+        let mut post = vec![];
+        for n in 1..=length as isize {
+            let p = if let Some(m) = base.filter(|m| n as usize <= m.post.len()) {
+                m.post[n as usize - 1]
+            } else if n == 1 {
+                0.5
+            } else {
+                let take = 0.5 * ((1. - g) * post_at(&post, n - 2) + g * post_at(&post, n - 4));
+                let drop = post_at(&post, n - 1);
+                let free = if n == 2 { params.free_drop } else { 0. };
+                take.min(drop) - free
+            };
+            post.push(p);
+        }
+
+        let mut met = Met {
+            pre: vec![vec![0.5; length]; length],
+            post,
+        };
+        if let Some(m) = base {
+            for (row, base_row) in met.pre.iter_mut().zip(&m.pre) {
+                row[..covered].copy_from_slice(base_row);
+            }
+        }
+        // the Crawford game has no cube
+        for b in covered.max(1) + 1..=length {
+            let lose = |k: usize| 1. - post_at(&met.post, b as isize - k as isize);
+            let e = 0.5 + 0.5 * ((1. - g) * lose(1) + g * lose(2));
+            met.pre[0][b - 1] = e;
+            met.pre[b - 1][0] = 1. - e;
+        }
+        let even = Probabilities {
+            win: 0.5,
+            win_gammon: 0.5 * g,
+            win_backgammon: 0.,
+            lose_gammon: 0.5 * g,
+            lose_backgammon: 0.,
+        };
+        for total in 4..=2 * length {
+            for a in 2..=length.min(total - 2) {
+                let b = total - a;
+                if b > length || b < a || b <= covered {
+                    continue;
+                }
+                let game = Match {
+                    score: (length - a, length - b),
+                    ..Match::with_length(length)
+                };
+                let e = {
+                    let lookup = |g: &Match| met.equity(g);
+                    let mut model = Model::with_game(
+                        params.cube_life,
+                        game,
+                        Player::White,
+                        even,
+                        1 << 12,
+                        &lookup,
+                    );
+                    model.equity(1, CubeState::Centered)
+                };
+                met.pre[a - 1][b - 1] = e;
+                met.pre[b - 1][a - 1] = 1. - e;
+            }
+        }
+        met
+    }
+
+    // the longest match covered
+    pub fn length(&self) -> usize {
+        self.pre.len()
+    }
+    // the chance of the player a-away against b-away
+    pub fn get(&self, a: usize, b: usize, crawford: bool) -> f64 {
+        if a == 0 {
+            return 1.;
+        }
+        if b == 0 {
+            return 0.;
+        }
+        if a == 1 && b > 1 && !crawford {
+            return 1. - self.post[b - 1];
+        }
+        if b == 1 && a > 1 && !crawford {
+            return self.post[a - 1];
+        }
+        self.pre[a - 1][b - 1]
+    }
+    // White's match winning chance
+    pub fn equity(&self, game: &Match) -> f64 {
+        let (w, b) = game.score;
+        let (aw, ab) = (
+            game.length - w.min(game.length),
+            game.length - b.min(game.length),
+        );
+        assert!(
+            aw <= self.length() && ab <= self.length(),
+            "match length {} beyond the table",
+            game.length
+        );
+        self.get(aw, ab, game.crawford)
+    }
+
+    // One row per line for the pre-Crawford table, then a line starting with "post"
+    // for the post-Crawford trailer. Commas or whitespace separate; # starts a comment.
+    pub fn parse_csv(text: &str) -> Result<Met, MetError> {
+        let mut pre = vec![];
+        let mut post = None;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields: Vec<&str> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|f| !f.is_empty())
+                .collect();
+            // only separators
+            if fields.is_empty() {
+                continue;
+            }
+            let is_post = fields[0].eq_ignore_ascii_case("post");
+            if is_post {
+                fields.remove(0);
+            }
+            let row = fields
+                .iter()
+                .map(|f| {
+                    f.parse::<f64>()
+                        .map_err(|_| MetError::BadNumber(n + 1, f.to_string()))
+                })
+                .collect::<Result<Vec<f64>, MetError>>()?;
+            if is_post {
+                post = Some(row);
+            } else {
+                pre.push(row);
+            }
+        }
+        Met::new(pre, post.ok_or(MetError::MissingPostCrawford)?)
+    }
+    pub fn write_csv(&self) -> String {
+        let row = |r: &[f64]| {
+            r.iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut s = String::new();
+        for r in &self.pre {
+            s += &row(r);
+            s += "\n";
+        }
+        s + "post," + &row(&self.post) + "\n"
+    }
+
+    // the explicit tables of a gnubg match equity file
+    pub fn parse_xml(text: &str) -> Result<Met, MetError> {
+        let pre = xml_table(text, "pre-crawford-table")?.ok_or(MetError::Empty)?;
+        let post = xml_table(text, "post-crawford-table")?
+            .and_then(|t| t.into_iter().next())
+            .ok_or(MetError::MissingPostCrawford)?;
+        Met::new(pre, post)
+    }
+
+    // gnubg XML if the text starts with a tag, CSV otherwise
+    pub fn parse(text: &str) -> Result<Met, MetError> {
+        if text.trim_start().starts_with('<') {
+            Met::parse_xml(text)
+        } else {
+            Met::parse_csv(text)
+        }
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Met, MetError> {
+        Met::parse(&std::fs::read_to_string(path).map_err(MetError::Io)?)
+    }
+}
+
+// the rows of <me> values inside the first element named `name`
+fn xml_table(text: &str, name: &str) -> Result<Option<Vec<Vec<f64>>>, MetError> {
+    let Some(start) = text.find(&format!("<{}", name)) else {
+        return Ok(None);
+    };
+    let end = text[start..]
+        .find(&format!("</{}>", name))
+        .map_or(text.len(), |e| start + e);
+    let body = &text[start..end];
+    let line = |i: usize| text[..start + i].lines().count();
+    let mut rows = vec![];
+    for (i, _) in body.match_indices("<row>") {
+        let row_end = body[i..].find("</row>").map_or(body.len(), |e| i + e);
+        let mut values = vec![];
+        for (j, _) in body[i..row_end].match_indices("<me>") {
+            let v = &body[i + j + 4..];
+            let v = v[..v.find('<').unwrap_or(v.len())].trim();
+            let v = v
+                .parse::<f64>()
+                .map_err(|_| MetError::BadNumber(line(i + j), v.to_owned()))?;
+            values.push(v);
+        }
+        rows.push(values);
+    }
+    Ok(Some(rows))
+}
+
+fn table() -> &'static RwLock<Arc<Met>> {
+    static TABLE: OnceLock<RwLock<Arc<Met>>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let met = Met::rockwell_kazaross().extend(25, &MetParams::default());
+        RwLock::new(Arc::new(met))
+    })
+}
+// the table behind fetch_match_equities, the default 25-point table unless replaced
+pub fn current() -> Arc<Met> {
+    table().read().unwrap().clone()
+}
+// the current table, first extended by generation if it is shorter than `length`
+pub fn covering(length: usize) -> Arc<Met> {
+    let met = current();
+    if met.length() >= length {
+        return met;
+    }
+    let mut table = table().write().unwrap();
+    if table.length() < length {
+        *table = Arc::new(table.extend(length, &MetParams::default()));
+    }
+    table.clone()
+}
+pub fn set_current(met: Met) {
+    *table().write().unwrap() = Arc::new(met);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generate() {
+        let params = MetParams::default();
+        let met = Met::generate(11, &params);
+        assert_eq!(met.length(), 11);
+        for a in 1..=11 {
+            assert!((met.get(a, a, false) - 0.5).abs() < 1e-12);
+            for b in 1..=11 {
+                let sum = met.get(a, b, true) + met.get(b, a, true);
+                assert!((sum - 1.).abs() < 1e-12);
+                if b < 11 {
+                    assert!(met.get(a, b + 1, true) > met.get(a, b, true));
+                }
+            }
+        }
+        assert_eq!(met.get(2, 1, false), 0.5 - params.free_drop);
+        assert!((met.get(2, 3, true) - 0.6).abs() < 0.02);
+        assert!((met.get(1, 2, true) - 0.68).abs() < 0.02);
+
+        let mut game = Match::with_length(11);
+        assert_eq!(met.equity(&game), 0.5);
+        game.add_score(Player::White, 10);
+        assert!(game.crawford);
+        assert_eq!(met.equity(&game), met.get(1, 11, true));
+        game.add_score(Player::Black, 1);
+        assert_eq!(met.equity(&game), 1. - met.get(10, 1, false));
+    }
+
+    #[test]
+    fn extend() {
+        let rk = Met::rockwell_kazaross();
+        let met = rk.extend(9, &MetParams::default());
+        assert_eq!(met.length(), 9);
+        for a in 1..=9 {
+            for b in 1..=9 {
+                if a <= 5 && b <= 5 {
+                    assert_eq!(met.get(a, b, true), rk.get(a, b, true));
+                    assert_eq!(met.get(a, b, false), rk.get(a, b, false));
+                }
+                let sum = met.get(a, b, true) + met.get(b, a, true);
+                assert!((sum - 1.).abs() < 1e-12);
+            }
+        }
+        assert!(met.get(1, 9, true) > met.get(1, 5, true));
+        assert_eq!(rk.extend(3, &MetParams::default()), rk);
+    }
+
+    #[test]
+    fn csv() {
+        let met = Met::generate(5, &MetParams::default());
+        let text = met.write_csv();
+        assert_eq!(text.lines().count(), 6);
+        let read = Met::parse_csv(&format!("# five points\n{}", text)).unwrap();
+        assert_eq!(read, met);
+        // a line of separators is skipped like a blank one
+        let read = Met::parse_csv(&format!(",,,\n{}", text)).unwrap();
+        assert_eq!(read, met);
+        assert!(Met::parse_csv(",,,\npost 0.5").is_err());
+        let err = Met::parse_csv("0.5 0.6\n0.4 x\npost 0.5 0.49");
+        assert!(matches!(err, Err(MetError::BadNumber(2, _))));
+        assert!(matches!(
+            Met::parse_csv("0.5 0.6\n0.4 0.5"),
+            Err(MetError::MissingPostCrawford)
+        ));
+        assert!(matches!(
+            Met::parse_csv("0.5 0.6\npost 0.5 0.49"),
+            Err(MetError::NotSquare)
+        ));
+    }
+
+    #[test]
+    fn xml() {
+        let text = r#"<?xml version="1.0"?>
+<met>
+  <info><name>Two points</name><length>2</length></info>
+  <pre-crawford-table type="explicit">
+    <row> <me>0.5</me> <me>0.7</me> </row>
+    <row> <me>0.3</me> <me>0.5</me> </row>
+  </pre-crawford-table>
+  <post-crawford-table player="both" type="explicit">
+    <row> <me>0.5</me> <me>0.48</me> </row>
+  </post-crawford-table>
+</met>"#;
+        let met = Met::parse_xml(text).unwrap();
+        assert_eq!(met.length(), 2);
+        assert_eq!(met.get(1, 2, true), 0.7);
+        assert_eq!(met.get(2, 1, false), 0.48);
+        assert!(matches!(
+            Met::parse_xml(&text.replace("0.48", "x")),
+            Err(MetError::BadNumber(9, _))
+        ));
+
+        assert_eq!(Met::parse(text).unwrap(), met);
+        assert!(matches!(Met::load("no/such/met.xml"), Err(MetError::Io(_))));
+    }
+}
@@ -0,0 +1,378 @@
+// Feed-forward network evaluator
+// One sigmoid hidden layer over a gnubg-style encoding of the position, and five
+// sigmoid outputs: win, gammon win, backgammon win, gammon loss and backgammon loss,
+// all from the side of the player on roll before rolling.
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::board::{Action, Board, DiceRoll, Pieces, Player, State};
+use crate::dice::Rng;
+use crate::evaluator::{
+    after_each, max_eq, terminal_equity, terminal_outcomes, Equities, Evaluator, Probabilities,
+};
+
+// four units for each of the 24 points and the bar, for both sides
+pub const INPUTS: usize = 2 * 25 * 4;
+pub const OUTPUTS: usize = 5;
+
+const MAGIC: &[u8; 4] = b"BGN1";
+
+// the player `p` is on roll
+pub fn encode(pieces: &Pieces, p: Player) -> Vec<f32> {
+    let mut inputs = Vec::with_capacity(INPUTS);
+    for side in [p, p.opponent()] {
+        for i in 1..=25 {
+            let n = if i == 25 {
+                pieces.bar(side)
+            } else {
+                pieces.point(side, i)
+            };
+            inputs.push((n == 1) as usize as f32);
+            inputs.push((n == 2) as usize as f32);
+            inputs.push((n >= 3) as usize as f32);
+            inputs.push(if n > 3 { (n - 3) as f32 / 2. } else { 0. });
+        }
+    }
+    inputs
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1. / (1. + (-x).exp())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Network {
+    hidden: usize,
+    // hidden × INPUTS, row by row
+    hidden_weights: Vec<f32>,
+    hidden_bias: Vec<f32>,
+    // OUTPUTS × hidden
+    output_weights: Vec<f32>,
+    output_bias: Vec<f32>,
+}
+
+impl Network {
+    // small random weights, the starting point for training
+    pub fn random(hidden: usize, rng: &mut Rng) -> Network {
+        let mut weights = |n: usize, scale: f64| -> Vec<f32> {
+            (0..n)
+                .map(|_| ((rng.unit() * 2. - 1.) * scale) as f32)
+                .collect()
+        };
+        Network {
+            hidden,
+            hidden_weights: weights(hidden * INPUTS, 0.1),
+            hidden_bias: weights(hidden, 0.1),
+            output_weights: weights(OUTPUTS * hidden, 0.1),
+            output_bias: vec![0.; OUTPUTS],
+        }
+    }
+    pub fn hidden(&self) -> usize {
+        self.hidden
+    }
+
+    pub fn forward(&self, inputs: &[f32]) -> [f32; OUTPUTS] {
+        self.activations(inputs).1
+    }
+    // the hidden layer and the outputs
+    fn activations(&self, inputs: &[f32]) -> (Vec<f32>, [f32; OUTPUTS]) {
+        assert_eq!(inputs.len(), INPUTS);
+        let hidden: Vec<f32> = self
+            .hidden_weights
+            .chunks(INPUTS)
+            .zip(&self.hidden_bias)
+            .map(|(w, b)| {
+                // the encoding is sparse
+                let x: f32 = w
+                    .iter()
+                    .zip(inputs)
+                    .filter(|(_, &x)| x != 0.)
+                    .map(|(w, x)| w * x)
+                    .sum();
+                sigmoid(x + b)
+            })
+            .collect();
+        let mut out = [0.; OUTPUTS];
+        for ((o, w), b) in out
+            .iter_mut()
+            .zip(self.output_weights.chunks(self.hidden))
+            .zip(&self.output_bias)
+        {
+            *o = sigmoid(w.iter().zip(&hidden).map(|(w, h)| w * h).sum::<f32>() + b);
+        }
+        (hidden, out)
+    }
+
+    // the number of weights and biases
+    pub fn parameters(&self) -> usize {
+        self.hidden * (INPUTS + 1) + OUTPUTS * (self.hidden + 1)
+    }
+    // all weights and biases, in file order
+    pub(crate) fn parameters_mut(&mut self) -> impl Iterator<Item = &mut f32> {
+        self.hidden_weights
+            .iter_mut()
+            .chain(self.hidden_bias.iter_mut())
+            .chain(self.output_weights.iter_mut())
+            .chain(self.output_bias.iter_mut())
+    }
+    // the outputs and, for each output, its gradient over the parameters in file order
+    pub(crate) fn gradients(&self, inputs: &[f32]) -> ([f32; OUTPUTS], Vec<Vec<f32>>) {
+        let h = self.hidden;
+        let (hidden, out) = self.activations(inputs);
+        let grads = (0..OUTPUTS)
+            .map(|k| {
+                let dz = out[k] * (1. - out[k]);
+                let v = &self.output_weights[k * h..(k + 1) * h];
+                let mut g = vec![0.; self.parameters()];
+                for j in 0..h {
+                    let da = dz * v[j] * hidden[j] * (1. - hidden[j]);
+                    for (g, x) in g[j * INPUTS..(j + 1) * INPUTS].iter_mut().zip(inputs) {
+                        *g = da * x;
+                    }
+                    g[h * INPUTS + j] = da;
+                    g[h * (INPUTS + 1) + k * h + j] = dz * hidden[j];
+                }
+                g[h * (INPUTS + 1) + OUTPUTS * h + k] = dz;
+                g
+            })
+            .collect();
+        (out, grads)
+    }
+
+    // cubeless probabilities from White's side, with the player `p` on roll
+    pub fn probabilities(&self, pieces: &Pieces, p: Player) -> Probabilities {
+        let out = self.forward(&encode(pieces, p));
+        let win = out[0] as f64;
+        // gammons are only possible while the loser has borne off nothing
+        let gammon = |loser: Player, chance: f32, limit: f64| {
+            if pieces.borne_off(loser) > 0 {
+                0.
+            } else {
+                (chance as f64).min(limit)
+            }
+        };
+        let win_gammon = gammon(p.opponent(), out[1], win);
+        let lose_gammon = gammon(p, out[3], 1. - win);
+        let prob = Probabilities {
+            win,
+            win_gammon,
+            win_backgammon: (out[2] as f64).min(win_gammon),
+            lose_gammon,
+            lose_backgammon: (out[4] as f64).min(lose_gammon),
+        };
+        if p == Player::White {
+            prob
+        } else {
+            prob.flip()
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        for n in [INPUTS, self.hidden, OUTPUTS] {
+            w.write_all(&(n as u32).to_le_bytes())?;
+        }
+        let weights = self
+            .hidden_weights
+            .iter()
+            .chain(&self.hidden_bias)
+            .chain(&self.output_weights)
+            .chain(&self.output_bias);
+        for v in weights {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Network> {
+        Network::read_from(std::fs::File::open(path)?)
+    }
+    pub fn read_from<R: Read>(mut r: R) -> io::Result<Network> {
+        let invalid = |s: &str| io::Error::new(io::ErrorKind::InvalidData, s.to_owned());
+        let mut bytes = vec![];
+        r.read_to_end(&mut bytes)?;
+        if bytes.len() < 16 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a network weights file"));
+        }
+        let size = |i: usize| {
+            u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]) as usize
+        };
+        let (inputs, hidden, outputs) = (size(4), size(8), size(12));
+        if inputs != INPUTS || outputs != OUTPUTS || hidden == 0 {
+            return Err(invalid("unsupported network shape"));
+        }
+        let weights = hidden
+            .checked_mul(INPUTS + 1 + OUTPUTS)
+            .map(|n| n + OUTPUTS);
+        if weights.and_then(|n| n.checked_mul(4)).map(|n| n + 16) != Some(bytes.len()) {
+            return Err(invalid("bad network weights size"));
+        }
+        let mut values = bytes[16..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        let mut read = |n: usize| -> Vec<f32> { (&mut values).take(n).collect() };
+        Ok(Network {
+            hidden,
+            hidden_weights: read(hidden * INPUTS),
+            hidden_bias: read(hidden),
+            output_weights: read(OUTPUTS * hidden),
+            output_bias: read(OUTPUTS),
+        })
+    }
+}
+
+// Static evaluation by the network, looking one move ahead for the checker play.
+// Cube decisions compare the cubeless equities with the cube as it would be.
+pub struct NeuralEvaluator {
+    pub net: Network,
+}
+impl NeuralEvaluator {
+    pub fn new(net: Network) -> NeuralEvaluator {
+        NeuralEvaluator { net }
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<NeuralEvaluator> {
+        Ok(NeuralEvaluator::new(Network::load(path)?))
+    }
+
+    pub fn probabilities(&self, board: &Board) -> Probabilities {
+        self.net.probabilities(&board.pieces, board.on_roll())
+    }
+
+    // White's equity of the position without searching
+    fn value(&self, board: &Board) -> f64 {
+        match board.state() {
+            State::Init | State::End | State::MatchEnd => terminal_equity(board),
+            State::ToDouble | State::ToRoll => self
+                .probabilities(board)
+                .equity(&board.game, board.cube.value()),
+            State::Doubled | State::ToMove | State::ToChoose => {
+                let eq = after_each(board.actions(), |a| self.after(board, a));
+                max_eq(&eq, board.player.unwrap()).1
+            }
+        }
+    }
+    fn after(&self, board: &Board, act: &Action) -> f64 {
+        let mut next = board.clone();
+        next.act(act);
+        self.value(&next)
+    }
+}
+
+impl Evaluator for NeuralEvaluator {
+    fn eval(&mut self, board: &Board) -> Equities {
+        match board.state() {
+            State::Init | State::MatchEnd => Equities::new(vec![], terminal_equity(board)),
+            State::End => {
+                let e = terminal_equity(board);
+                Equities::new(vec![(Action::Reset, e)], e).with_outcomes(terminal_outcomes(board))
+            }
+            State::ToRoll => {
+                let rolls = DiceRoll::all().into_iter().map(Action::Roll);
+                Equities::new(
+                    after_each(rolls, |a| self.after(board, a)),
+                    self.value(board),
+                )
+                .with_outcomes(Some(self.probabilities(board)))
+            }
+            State::ToDouble | State::Doubled | State::ToMove | State::ToChoose => {
+                let eq = after_each(board.actions(), |a| self.after(board, a));
+                let e = max_eq(&eq, board.player.unwrap()).1;
+                Equities::new(eq, e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::Dice;
+
+    #[test]
+    fn encoding() {
+        let b = Board::new();
+        let inputs = encode(&b.pieces, Player::White);
+        assert_eq!(inputs.len(), INPUTS);
+        // the 24 point has two checkers, the 6 point five
+        assert_eq!(inputs[23 * 4..24 * 4], [0., 1., 0., 0.]);
+        assert_eq!(inputs[5 * 4..6 * 4], [0., 0., 1., 1.]);
+        // the starting position looks the same from both sides
+        assert_eq!(encode(&b.pieces, Player::Black), inputs);
+        let sum: f32 = inputs.iter().sum();
+        assert_eq!(sum, 2. * 6.);
+    }
+
+    #[test]
+    fn probabilities() {
+        let net = Network::random(8, &mut Rng::new(1));
+        let b = Board::new();
+        let p = net.probabilities(&b.pieces, Player::White);
+        assert!(p.win > 0. && p.win < 1.);
+        assert!(p.win_backgammon <= p.win_gammon && p.win_gammon <= p.win);
+        assert!(p.lose_backgammon <= p.lose_gammon && p.lose_gammon <= 1. - p.win);
+        // the same position from the other side
+        assert_eq!(net.probabilities(&b.pieces, Player::Black), p.flip());
+
+        // Black has borne off a checker, so White cannot win a gammon
+        let b = Board::from_xg_id("XGID=-a----E-C---eE---c-e----B-:0:0:1:00:0:0:0:0:10").unwrap();
+        assert_eq!(net.probabilities(&b.pieces, Player::White).win_gammon, 0.);
+    }
+
+    #[test]
+    fn gradients() {
+        let net = Network::random(3, &mut Rng::new(4));
+        let inputs = encode(&Board::new().pieces, Player::White);
+        let (out, grads) = net.gradients(&inputs);
+        assert_eq!(out, net.forward(&inputs));
+        // against finite differences, for a few parameters of each layer
+        let n = net.parameters();
+        for i in [0, 5, 3 * INPUTS + 1, 3 * (INPUTS + 1) + 4, n - 1] {
+            let mut moved = net.clone();
+            *moved.parameters_mut().nth(i).unwrap() += 1e-2;
+            let after = moved.forward(&inputs);
+            for k in 0..OUTPUTS {
+                let diff = (after[k] - out[k]) / 1e-2;
+                assert!((diff - grads[k][i]).abs() < 1e-3, "{} {}", i, k);
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let net = Network::random(5, &mut Rng::new(2));
+        let mut bytes = vec![];
+        net.write_to(&mut bytes).unwrap();
+        assert_eq!(Network::read_from(&bytes[..]).unwrap(), net);
+        assert!(Network::read_from(&b"BGN1"[..]).is_err());
+        // a length that is not whole values is an error, not a panic
+        let mut short = b"BGN1".to_vec();
+        short.extend([0; 13]);
+        let e = Network::read_from(&short[..]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        bytes.pop();
+        assert!(Network::read_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn evaluator() {
+        let mut e = NeuralEvaluator::new(Network::random(8, &mut Rng::new(3)));
+        let mut b = Board::new();
+        b.act(&Action::InitRoll(Dice(3, 1)));
+        let eq = e.eval(&b);
+        assert_eq!(eq.actions.len(), b.actions().len());
+        assert_eq!(eq.equity, max_eq(&eq.actions, Player::White).1);
+        assert!(eq.equity > 0. && eq.equity < 1.);
+
+        b.act(&eq.actions[0].0);
+        let eq = e.eval(&b);
+        let acts: Vec<Action> = eq.actions.iter().map(|(a, _)| a.clone()).collect();
+        assert_eq!(acts, b.actions());
+
+        b.act(&Action::NoDouble);
+        assert_eq!(e.eval(&b).actions.len(), 21);
+    }
+}
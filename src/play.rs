@@ -0,0 +1,104 @@
+use crate::agent::Agent;
+use crate::board::{Action, Board, Player, State};
+use crate::dice::DiceSource;
+
+// Plays until the match ends, or until the game ends for money.
+// The agents choose in ToDouble, Doubled and ToMove; the rest is driven here.
+// Returns the played actions.
+pub fn play_match(
+    board: &mut Board,
+    white: &mut dyn Agent,
+    black: &mut dyn Agent,
+    dice: &mut dyn DiceSource,
+) -> Vec<Action> {
+    let mut history = vec![];
+    loop {
+        let act = match board.state() {
+            State::Init => Action::InitRoll(dice.init_roll()),
+            State::ToRoll => Action::Roll(dice.roll()),
+            State::End if board.game.is_money() => break,
+            State::End => Action::Reset,
+            State::MatchEnd => break,
+            State::ToDouble | State::Doubled | State::ToMove | State::ToChoose => {
+                let act = match board.player {
+                    Some(Player::White) => white.act(board),
+                    _ => black.act(board),
+                };
+                assert!(board.actions().contains(&act), "illegal action {:?}", act);
+                act
+            }
+        };
+        board.act(&act);
+        history.push(act);
+    }
+    history
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{FirstLegalAgent, RandomAgent};
+    use crate::board::{Dice, Match, Move, Transcript};
+    use crate::dice::{Rng, Scripted};
+
+    struct Doubler;
+    impl Agent for Doubler {
+        fn double(&mut self, _: &Board) -> bool {
+            true
+        }
+        fn take(&mut self, _: &Board) -> bool {
+            false
+        }
+        fn play(&mut self, _: &Board, moves: &[Move]) -> Move {
+            moves[0].clone()
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        let play = |seed| {
+            let mut board = Board::new();
+            board.game = Match::with_length(3);
+            let history = play_match(
+                &mut board,
+                &mut RandomAgent::new(Rng::new(seed)),
+                &mut FirstLegalAgent,
+                &mut Rng::new(seed),
+            );
+            (board, history)
+        };
+        let (board, history) = play(1);
+        assert_eq!(board.state(), State::MatchEnd);
+        assert_eq!(play(1).1, history);
+        assert_ne!(play(2).1, history);
+
+        let mut t = Transcript::new(3, "White", "Black");
+        t.actions = history;
+        assert_eq!(t.replay(), board);
+    }
+
+    #[test]
+    fn double_pass() {
+        let mut board = Board::new();
+        let mut dice = Scripted::new(vec![Dice(3, 1)]);
+        // White plays 31, Black doubles and White passes
+        let history = play_match(&mut board, &mut Doubler, &mut Doubler, &mut dice);
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[2], Action::Double);
+        assert_eq!(history[3], Action::Pass);
+        assert_eq!(board.game.winner(), Some(Player::Black));
+    }
+
+    #[test]
+    fn money_game() {
+        let mut board = Board::new();
+        board.game = Match::money(false, false);
+        play_match(
+            &mut board,
+            &mut FirstLegalAgent,
+            &mut FirstLegalAgent,
+            &mut Rng::new(7),
+        );
+        assert_eq!(board.state(), State::End);
+    }
+}
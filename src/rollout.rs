@@ -0,0 +1,234 @@
+// Monte Carlo rollouts
+// Every candidate action is played out the same number of times with the same dice,
+// the evaluator deciding for both sides. With luck adjustment the equity of each roll
+// minus the average over all rolls, as the evaluator sees them, is taken off the result.
+use crate::board::{Action, Board, Dice, Player, State};
+use crate::dice::{DiceSource, Rng};
+use crate::evaluator::{max_eq, terminal_equity, Evaluator, Probabilities};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RolloutConfig {
+    pub games: usize,
+    // rolls before the evaluator scores the position; None plays to the end
+    pub truncate: Option<usize>,
+    pub luck_adjust: bool,
+    // game i of every candidate uses the dice seeded with seed + i
+    pub seed: u64,
+}
+impl Default for RolloutConfig {
+    fn default() -> Self {
+        RolloutConfig {
+            games: 1296,
+            truncate: None,
+            luck_adjust: true,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RolloutResult {
+    pub action: Action,
+    // White's mean equity and its standard error
+    pub equity: f64,
+    pub std_error: f64,
+    pub games: usize,
+    // the games played to the end, and their outcomes from White's side
+    pub finished: usize,
+    pub outcomes: Probabilities,
+}
+
+// White's equity of one game, and the winner and points per cube value if it ended
+fn play_out<E: Evaluator>(
+    board: &mut Board,
+    evaluator: &mut E,
+    dice: &mut dyn DiceSource,
+    config: &RolloutConfig,
+) -> (f64, Option<(Player, usize)>) {
+    let mut rolls = 0;
+    let mut luck = 0.;
+    loop {
+        let act = match board.state() {
+            State::Init | State::End | State::MatchEnd => {
+                let e = terminal_equity(board);
+                let r = board
+                    .result
+                    .map(|r| (r.player, r.score / board.cube.value()));
+                return (e - luck, r);
+            }
+            State::ToDouble | State::ToRoll if config.truncate == Some(rolls) => {
+                return (evaluator.eval(board).equity - luck, None);
+            }
+            State::ToRoll => {
+                let d = dice.roll();
+                rolls += 1;
+                if config.luck_adjust {
+                    let eq = evaluator.eval(board).actions;
+                    let mean: f64 = eq
+                        .iter()
+                        .map(|(a, e)| match a {
+                            Action::Roll(d) => d.prob() * e,
+                            _ => 0.,
+                        })
+                        .sum();
+                    let same =
+                        |r: &Dice| r.0.min(r.1) == d.0.min(d.1) && r.0.max(r.1) == d.0.max(d.1);
+                    let e = eq
+                        .iter()
+                        .find(|(a, _)| matches!(a, Action::Roll(r) if same(r)))
+                        .map_or(mean, |(_, e)| *e);
+                    luck += e - mean;
+                }
+                Action::Roll(d)
+            }
+            State::ToDouble | State::Doubled | State::ToMove | State::ToChoose => {
+                let eq = evaluator.eval(board).actions;
+                max_eq(&eq, board.player.unwrap()).0
+            }
+        };
+        board.act(&act);
+    }
+}
+
+// Rolls out every candidate from `board`, which must be a legal action there.
+pub fn rollout<E: Evaluator>(
+    board: &Board,
+    candidates: &[Action],
+    evaluator: &mut E,
+    config: &RolloutConfig,
+) -> Vec<RolloutResult> {
+    candidates
+        .iter()
+        .map(|action| {
+            assert!(board.actions().contains(action), "illegal {:?}", action);
+            let mut sum = 0.;
+            let mut squares = 0.;
+            let mut finished = 0;
+            // wins, gammons and backgammons for White and for Black
+            let mut counts = [[0usize; 3]; 2];
+            for i in 0..config.games {
+                let mut dice = Rng::new(config.seed.wrapping_add(i as u64));
+                let mut b = board.clone();
+                b.act(action);
+                let (e, result) = play_out(&mut b, evaluator, &mut dice, config);
+                sum += e;
+                squares += e * e;
+                if let Some((winner, points)) = result {
+                    finished += 1;
+                    let c = &mut counts[(winner == Player::Black) as usize];
+                    for (k, c) in c.iter_mut().enumerate() {
+                        *c += (points > k) as usize;
+                    }
+                }
+            }
+            let n = config.games as f64;
+            let equity = sum / n;
+            let variance = if config.games > 1 {
+                ((squares - n * equity * equity) / (n - 1.)).max(0.)
+            } else {
+                0.
+            };
+            let rate = |c: usize| {
+                if finished == 0 {
+                    0.
+                } else {
+                    c as f64 / finished as f64
+                }
+            };
+            RolloutResult {
+                action: action.clone(),
+                equity,
+                std_error: (variance / n).sqrt(),
+                games: config.games,
+                finished,
+                outcomes: Probabilities {
+                    win: rate(counts[0][0]),
+                    win_gammon: rate(counts[0][1]),
+                    win_backgammon: rate(counts[0][2]),
+                    lose_gammon: rate(counts[1][1]),
+                    lose_backgammon: rate(counts[1][2]),
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bearoff::{BearoffEvaluator, OneSided};
+
+    fn bearoff() -> BearoffEvaluator {
+        BearoffEvaluator::new(OneSided::generate(6, 3))
+    }
+
+    #[test]
+    fn exact() {
+        // White to play 21 with checkers on the 4, 3 and 2 points against two Black checkers
+        let b = Board::from_xg_id("XGID=--AAA-----------------aa--:0:0:1:21:0:0:0:0:10").unwrap();
+        let candidates = b.actions();
+        let mut e = bearoff();
+        let exact = e.eval(&b);
+        let config = RolloutConfig {
+            games: 50,
+            ..RolloutConfig::default()
+        };
+        let results = rollout(&b, &candidates, &mut e, &config);
+        assert_eq!(results.len(), candidates.len());
+        for (r, (a, eq)) in results.iter().zip(&exact.actions) {
+            // an exact evaluator leaves nothing to luck
+            assert_eq!(&r.action, a);
+            assert!((r.equity - eq).abs() < 1e-5, "{:?} {}", r, eq);
+            assert!(r.std_error < 1e-6);
+            assert_eq!(r.finished, 50);
+            assert!(r.outcomes.win > 0. && r.outcomes.win < 1.);
+        }
+    }
+
+    #[test]
+    fn duplicate_dice() {
+        let b = Board::from_xg_id("XGID=--AAA-----------------aa--:0:0:1:21:0:0:0:0:10").unwrap();
+        let m = b.actions()[0].clone();
+        let config = RolloutConfig {
+            games: 20,
+            luck_adjust: false,
+            seed: 5,
+            ..RolloutConfig::default()
+        };
+        let r = rollout(&b, &[m.clone(), m], &mut bearoff(), &config);
+        assert_eq!(r[0], r[1]);
+        assert!(r[0].std_error > 0.);
+        assert_eq!(r[0].outcomes.win_gammon, 0.);
+
+        // truncated at once, the rollout is the evaluation
+        let config = RolloutConfig {
+            truncate: Some(0),
+            ..config
+        };
+        let mut e = bearoff();
+        let r = rollout(&b, &b.actions(), &mut e, &config);
+        let exact = e.eval(&b);
+        assert_eq!(r[1].finished, 0);
+        assert!((r[1].equity - exact.actions[1].1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cube() {
+        // Black on roll bears off the last checker from its 4 point with any roll but 21
+        let b = Board::from_xg_id("XGID=-A-------------------a----:0:0:-1:00:0:0:0:0:10").unwrap();
+        let config = RolloutConfig {
+            games: 100,
+            luck_adjust: false,
+            ..RolloutConfig::default()
+        };
+        let r = rollout(
+            &b,
+            &[Action::NoDouble, Action::Double],
+            &mut bearoff(),
+            &config,
+        );
+        // the double is passed
+        assert_eq!(r[1].equity, -1.);
+        assert!(r[0].equity > -1. && r[0].equity < 0.);
+    }
+}
@@ -0,0 +1,193 @@
+// Depth-limited expectiminimax
+// A ply is one roll and move of a player. At zero plies the positions after each move
+// are scored by the leaf evaluator; every further ply averages the 21 rolls of the
+// next player, who picks the best of the top-k moves by the leaf evaluator.
+// Doubling is only considered at the root.
+use crate::board::{Action, Board, DiceRoll, Player, State};
+use crate::evaluator::{after_each, max_eq, terminal_equity, Equities, Evaluator};
+
+pub struct NPlyEvaluator<E: Evaluator> {
+    pub leaf: E,
+    pub plies: usize,
+    // moves expanded at each ply beyond the first look; the rest are dropped
+    pub top_k: usize,
+}
+
+impl<E: Evaluator> NPlyEvaluator<E> {
+    pub fn new(leaf: E, plies: usize, top_k: usize) -> NPlyEvaluator<E> {
+        assert!(top_k > 0);
+        NPlyEvaluator { leaf, plies, top_k }
+    }
+
+    // like eval, with the depth for this call
+    pub fn search(&mut self, board: &Board, plies: usize) -> Equities {
+        match board.state() {
+            State::Init | State::MatchEnd => Equities::new(vec![], terminal_equity(board)),
+            State::End => {
+                let e = terminal_equity(board);
+                Equities::new(vec![(Action::Reset, e)], e)
+            }
+            // counted as in value: the roll is the ply
+            State::ToRoll if plies == 0 => self.leaf.eval(board),
+            State::ToRoll => {
+                let mut e = 0.;
+                let rolls = DiceRoll::all()
+                    .into_iter()
+                    .map(|d| {
+                        let v = self.after(board, &Action::Roll(d), plies - 1);
+                        e += d.prob() * v;
+                        (Action::Roll(d), v)
+                    })
+                    .collect();
+                Equities::new(rolls, e)
+            }
+            State::ToMove => {
+                let eq = self.moves(board, plies);
+                let e = max_eq(&eq, board.player.unwrap()).1;
+                Equities::new(eq, e)
+            }
+            State::ToDouble | State::Doubled | State::ToChoose => {
+                let eq = after_each(board.actions(), |a| self.after(board, a, plies));
+                let e = max_eq(&eq, board.player.unwrap()).1;
+                Equities::new(eq, e)
+            }
+        }
+    }
+
+    // White's equity with `plies` rolls still to expand
+    fn value(&mut self, board: &Board, plies: usize) -> f64 {
+        match board.state() {
+            State::Init | State::End | State::MatchEnd => terminal_equity(board),
+            State::ToDouble | State::ToRoll if plies == 0 => self.leaf.eval(board).equity,
+            State::ToDouble => self.after(board, &Action::NoDouble, plies),
+            State::ToRoll => DiceRoll::all()
+                .into_iter()
+                .map(|d| d.prob() * self.after(board, &Action::Roll(d), plies - 1))
+                .sum(),
+            State::Doubled | State::ToChoose => {
+                let eq = after_each(board.actions(), |a| self.after(board, a, plies));
+                max_eq(&eq, board.player.unwrap()).1
+            }
+            State::ToMove => {
+                let eq = self.moves(board, plies);
+                max_eq(&eq, board.player.unwrap()).1
+            }
+        }
+    }
+    fn after(&mut self, board: &Board, act: &Action, plies: usize) -> f64 {
+        let mut next = board.clone();
+        next.act(act);
+        self.value(&next, plies)
+    }
+
+    // Without plies left the leaf values are final; otherwise only the top-k moves
+    // by the leaf evaluator are searched further.
+    fn moves(&mut self, board: &Board, plies: usize) -> Vec<(Action, f64)> {
+        let mut eq = after_each(board.actions(), |a| self.after(board, a, 0));
+        if plies == 0 {
+            return eq;
+        }
+        if eq.len() > self.top_k {
+            // best first for the player to move
+            let sign = if board.player == Some(Player::White) {
+                1.
+            } else {
+                -1.
+            };
+            eq.sort_by(|(_, a), (_, b)| (sign * b).total_cmp(&(sign * a)));
+            eq.truncate(self.top_k);
+        }
+        after_each(eq.into_iter().map(|(a, _)| a), |a| {
+            self.after(board, a, plies)
+        })
+    }
+}
+
+impl<E: Evaluator> Evaluator for NPlyEvaluator<E> {
+    fn eval(&mut self, board: &Board) -> Equities {
+        self.search(board, self.plies)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bearoff::{BearoffEvaluator, OneSided};
+
+    // race by pips, counting its calls
+    struct Pips(usize);
+    impl Evaluator for Pips {
+        fn eval(&mut self, board: &Board) -> Equities {
+            self.0 += 1;
+            let ps = &board.pieces;
+            let race = ps.pips(Player::Black) as f64 - ps.pips(Player::White) as f64;
+            Equities::new(vec![], 0.5 + race / 100.)
+        }
+    }
+
+    #[test]
+    fn exact_leaf() {
+        let b = Board::from_xg_id("XGID=--AAA-----------------aa--:0:0:1:21:0:0:0:3:10").unwrap();
+        let mut exact = BearoffEvaluator::new(OneSided::generate(6, 3));
+        let want = exact.eval(&b);
+        for plies in 0..=2 {
+            let mut e =
+                NPlyEvaluator::new(BearoffEvaluator::new(OneSided::generate(6, 3)), plies, 10);
+            let got = e.eval(&b);
+            assert_eq!(got.actions.len(), want.actions.len());
+            for ((a, x), (b, y)) in got.actions.iter().zip(&want.actions) {
+                assert_eq!(a, b);
+                assert!((x - y).abs() < 1e-5, "{} plies: {} {}", plies, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn roll_at_root() {
+        // on roll at the root, as deep as the same position inside the tree
+        let mut b =
+            Board::from_xg_id("XGID=-BBBB----------------bbbb-:0:0:1:00:0:0:0:3:10").unwrap();
+        b.act(&Action::NoDouble);
+        assert_eq!(b.state(), State::ToRoll);
+        let mut e = NPlyEvaluator::new(Pips(0), 0, 2);
+        for plies in 0..=2 {
+            assert_eq!(e.search(&b, plies).equity, e.value(&b, plies));
+        }
+        assert_eq!(e.search(&b, 0).equity, Pips(0).eval(&b).equity);
+    }
+
+    #[test]
+    fn depth_and_filter() {
+        let b = Board::from_xg_id("XGID=-BBBB----------------bbbb-:0:0:1:31:0:0:0:3:10").unwrap();
+        let moves = b.actions().len();
+        let mut e = NPlyEvaluator::new(Pips(0), 0, 2);
+        assert_eq!(e.eval(&b).actions.len(), moves);
+        assert_eq!(e.leaf.0, moves);
+
+        e.leaf.0 = 0;
+        let eq = e.search(&b, 1);
+        assert_eq!(eq.actions.len(), 2);
+        // the first look at every move, then every reply to each roll after the top two
+        let replies: usize = eq
+            .actions
+            .iter()
+            .map(|(a, _)| {
+                let mut next = b.clone();
+                next.act(a);
+                if next.state() == State::ToDouble {
+                    next.act(&Action::NoDouble);
+                }
+                DiceRoll::all()
+                    .into_iter()
+                    .map(|d| {
+                        let mut n = next.clone();
+                        n.act(&Action::Roll(d));
+                        n.actions().len()
+                    })
+                    .sum::<usize>()
+            })
+            .sum();
+        assert_eq!(e.leaf.0, moves + replies);
+        assert_eq!(e.plies, 0);
+    }
+}
@@ -0,0 +1,336 @@
+// TD(λ) self-play training for the network evaluator
+// Both sides play the move the network likes best, never doubling. After every move
+// the outputs for the previous position are pulled towards those of the next one,
+// seen from the other side, and towards the result once the game is over.
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::board::{Action, Board, Match, Player, State};
+use crate::dice::{DiceSource, Rng};
+use crate::evaluator::Probabilities;
+use crate::neural::{encode, Network, OUTPUTS};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BenchmarkError {
+    // line number, starting at 1
+    BadLine(usize, String),
+}
+impl fmt::Display for BenchmarkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BenchmarkError::BadLine(n, s) => write!(f, "line {}: {}", n, s),
+        }
+    }
+}
+impl std::error::Error for BenchmarkError {}
+
+// Positions before the roll with their reference probabilities from White's side.
+// The text form has one position per line: the XGID and the five probabilities.
+#[derive(Debug, Clone, Default)]
+pub struct Benchmark {
+    pub positions: Vec<(Board, Probabilities)>,
+}
+impl Benchmark {
+    pub fn new(positions: Vec<(Board, Probabilities)>) -> Benchmark {
+        Benchmark { positions }
+    }
+
+    pub fn parse(text: &str) -> Result<Benchmark, BenchmarkError> {
+        let mut positions = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = |s: String| BenchmarkError::BadLine(n + 1, s);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 1 + OUTPUTS {
+                return Err(bad(format!("expected an XGID and {} numbers", OUTPUTS)));
+            }
+            let board = Board::from_xg_id(fields[0]).map_err(|e| bad(e.to_string()))?;
+            let mut p = [0.; OUTPUTS];
+            for (p, f) in p.iter_mut().zip(&fields[1..]) {
+                *p = f
+                    .parse()
+                    .map_err(|_| bad(format!("bad probability {}", f)))?;
+            }
+            positions.push((
+                board,
+                Probabilities {
+                    win: p[0],
+                    win_gammon: p[1],
+                    win_backgammon: p[2],
+                    lose_gammon: p[3],
+                    lose_backgammon: p[4],
+                },
+            ));
+        }
+        Ok(Benchmark { positions })
+    }
+    pub fn write(&self) -> String {
+        self.positions
+            .iter()
+            .map(|(b, p)| {
+                format!(
+                    "{} {} {} {} {} {}\n",
+                    b.xg_id(),
+                    p.win,
+                    p.win_gammon,
+                    p.win_backgammon,
+                    p.lose_gammon,
+                    p.lose_backgammon
+                )
+            })
+            .collect()
+    }
+
+    // mean absolute error of the cubeless money equity
+    pub fn error(&self, net: &Network) -> f64 {
+        if self.positions.is_empty() {
+            return 0.;
+        }
+        let money = Match::money(false, false);
+        let total: f64 = self
+            .positions
+            .iter()
+            .map(|(b, p)| {
+                let q = net.probabilities(&b.pieces, b.on_roll());
+                (q.equity(&money, 1) - p.equity(&money, 1)).abs()
+            })
+            .sum();
+        total / self.positions.len() as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrainConfig {
+    pub alpha: f32,
+    pub lambda: f32,
+    pub games_per_epoch: usize,
+    // the weights are written here after every epoch
+    pub checkpoint: Option<PathBuf>,
+}
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            alpha: 0.1,
+            lambda: 0.7,
+            games_per_epoch: 1000,
+            checkpoint: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EpochReport {
+    // starting at 1
+    pub epoch: usize,
+    pub games: usize,
+    // average number of moves per game
+    pub moves: f64,
+    pub error: f64,
+}
+
+pub struct Trainer {
+    pub net: Network,
+    pub config: TrainConfig,
+    pub epoch: usize,
+    dice: Rng,
+}
+
+// the outputs of the position seen from the other side
+fn flip(o: [f32; OUTPUTS]) -> [f32; OUTPUTS] {
+    [1. - o[0], o[3], o[4], o[1], o[2]]
+}
+
+impl Trainer {
+    pub fn new(net: Network, config: TrainConfig, dice: Rng) -> Trainer {
+        Trainer {
+            net,
+            config,
+            epoch: 0,
+            dice,
+        }
+    }
+
+    // the move the network likes best for the player on roll
+    fn choose(&self, board: &Board) -> Action {
+        let p = board.player.unwrap();
+        let money = Match::money(false, false);
+        let sign = if p == Player::White { 1. } else { -1. };
+        let value = |a: &Action| {
+            let mut next = board.clone();
+            next.act(a);
+            let e = match next.result {
+                Some(r) if r.player == Player::White => r.score as f64,
+                Some(r) => -(r.score as f64),
+                None => self
+                    .net
+                    .probabilities(&next.pieces, p.opponent())
+                    .equity(&money, 1),
+            };
+            sign * e
+        };
+        board
+            .actions()
+            .into_iter()
+            .map(|a| (value(&a), a))
+            .max_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap())
+            .unwrap()
+            .1
+    }
+
+    // plays one cubeless game against itself, learning along the way;
+    // returns the number of moves
+    pub fn play_game(&mut self) -> usize {
+        let mut board = Board::new();
+        board.game = Match::money(false, false);
+        let params = self.net.parameters();
+        let mut traces = vec![vec![0.; params]; OUTPUTS];
+        // the outputs and gradients for the last position before a roll
+        let mut last: Option<([f32; OUTPUTS], Vec<Vec<f32>>)> = None;
+        let mut moves = 0;
+        loop {
+            let act = match board.state() {
+                State::Init => Action::InitRoll(self.dice.init_roll()),
+                State::ToDouble => Action::NoDouble,
+                State::ToRoll => {
+                    let now = self.net.gradients(&encode(&board.pieces, board.on_roll()));
+                    if let Some(prev) = last.take() {
+                        self.learn(&mut traces, prev, flip(now.0));
+                    }
+                    last = Some(now);
+                    Action::Roll(self.dice.roll())
+                }
+                State::ToMove => {
+                    moves += 1;
+                    if last.is_none() {
+                        // the opening roll: the player on roll is before the roll
+                        last = Some(self.net.gradients(&encode(&board.pieces, board.on_roll())));
+                    }
+                    self.choose(&board)
+                }
+                State::End | State::MatchEnd => break,
+                State::Doubled => unreachable!("nobody doubles"),
+                State::ToChoose => unreachable!("the standard game"),
+            };
+            board.act(&act);
+        }
+        let result = board.result.unwrap();
+        let won = [
+            1.,
+            (result.score >= 2) as u8 as f32,
+            (result.score >= 3) as u8 as f32,
+        ];
+        let winner = [won[0], won[1], won[2], 0., 0.];
+        if let Some(prev) = last {
+            // the position before the last roll belongs to the winner
+            self.learn(&mut traces, prev, winner);
+        }
+        moves
+    }
+
+    fn learn(
+        &mut self,
+        traces: &mut [Vec<f32>],
+        (out, grads): ([f32; OUTPUTS], Vec<Vec<f32>>),
+        target: [f32; OUTPUTS],
+    ) {
+        let (alpha, lambda) = (self.config.alpha, self.config.lambda);
+        // the earlier positions were seen from the other side
+        traces.swap(1, 3);
+        traces.swap(2, 4);
+        for (k, (e, g)) in traces.iter_mut().zip(&grads).enumerate() {
+            let decay = if k == 0 { -lambda } else { lambda };
+            for (e, g) in e.iter_mut().zip(g) {
+                *e = decay * *e + g;
+            }
+        }
+        let delta: Vec<f32> = target.iter().zip(out).map(|(t, o)| t - o).collect();
+        for (i, w) in self.net.parameters_mut().enumerate() {
+            *w += alpha * (0..OUTPUTS).map(|k| delta[k] * traces[k][i]).sum::<f32>();
+        }
+    }
+
+    // plays an epoch of games, writes the checkpoint and measures the benchmark error
+    pub fn epoch(&mut self, benchmark: &Benchmark) -> io::Result<EpochReport> {
+        let games = self.config.games_per_epoch;
+        let moves: usize = (0..games).map(|_| self.play_game()).sum();
+        self.epoch += 1;
+        if let Some(path) = &self.config.checkpoint {
+            self.net.save(path)?;
+        }
+        Ok(EpochReport {
+            epoch: self.epoch,
+            games,
+            moves: moves as f64 / games.max(1) as f64,
+            error: benchmark.error(&self.net),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bearoff::{BearoffEvaluator, OneSided};
+
+    #[test]
+    fn benchmark() {
+        let text = "# short races\n\
+            XGID=-A-A------------------a-a-:0:0:1:00:0:0:0:0:10 0.75 0 0 0 0\n\
+            \n\
+            XGID=---B------------------aa--:0:0:-1:00:0:0:0:0:10 0.5 0 0 0 0\n";
+        let b = Benchmark::parse(text).unwrap();
+        assert_eq!(b.positions.len(), 2);
+        assert_eq!(b.positions[0].1.win, 0.75);
+        assert_eq!(Benchmark::parse(&b.write()).unwrap().positions, b.positions);
+
+        let err = Benchmark::parse("XGID=-A-A------------------a-a-:0:0:1:00:0:0:0:0:10 0.75");
+        assert!(matches!(err, Err(BenchmarkError::BadLine(1, _))));
+        assert!(Benchmark::parse("XGID=foo 1 0 0 0 0").is_err());
+    }
+
+    #[test]
+    fn learns() {
+        // exact probabilities of a few short races
+        let exact = BearoffEvaluator::new(OneSided::generate(6, 2));
+        let xgids = [
+            "XGID=-A-A------------------a-a-:0:0:1:00:0:0:0:0:10",
+            "XGID=-----B----------------aa--:0:0:1:00:0:0:0:0:10",
+            "XGID=-B----------------------b-:0:0:-1:00:0:0:0:0:10",
+        ];
+        let positions = xgids
+            .iter()
+            .map(|x| {
+                let b = Board::from_xg_id(x).unwrap();
+                let p = exact.probabilities(&b).unwrap();
+                (b, p)
+            })
+            .collect();
+        let bench = Benchmark::new(positions);
+
+        // removes the checkpoint even when an assertion fails
+        struct Remove(std::path::PathBuf);
+        impl Drop for Remove {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+        let path = Remove(std::env::temp_dir().join(format!("td-{}.bin", std::process::id())));
+        let config = TrainConfig {
+            games_per_epoch: 3,
+            checkpoint: Some(path.0.clone()),
+            ..TrainConfig::default()
+        };
+        let net = Network::random(4, &mut Rng::new(1));
+        let before = net.clone();
+        let mut t = Trainer::new(net, config, Rng::new(2));
+        let report = t.epoch(&bench).unwrap();
+        assert_eq!(report.epoch, 1);
+        assert!(report.moves > 0.);
+        assert!(report.error >= 0.);
+        assert_ne!(t.net, before);
+        assert_eq!(Network::load(&path.0).unwrap(), t.net);
+    }
+}
@@ -0,0 +1,176 @@
+use wasm_bindgen::prelude::*;
+
+use crate::board::{Action, Board, Match};
+use crate::evaluator::{Evaluator, OpenEvaluator};
+use crate::met::{self, Met, MetError};
+
+// Replaces the match equity table by a CSV or gnubg XML table. The table is global to
+// the module: every Game, those already made included, evaluates with it from then on,
+// though equities an evaluator has already worked out are not redone.
+#[wasm_bindgen(js_name = loadMet)]
+pub fn load_met(text: &str) -> Result<(), JsValue> {
+    try_load_met(text).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+fn try_load_met(text: &str) -> Result<(), MetError> {
+    met::set_current(Met::parse(text)?);
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub struct Game {
+    board: Board,
+    evaluator: OpenEvaluator,
+}
+
+#[wasm_bindgen]
+impl Game {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Game {
+        Game::with_board(Board::new())
+    }
+    #[wasm_bindgen(js_name = newMatch)]
+    pub fn new_match(length: usize) -> Game {
+        let mut board = Board::new();
+        board.game = Match::with_length(length);
+        Game::with_board(board)
+    }
+    #[wasm_bindgen(js_name = newMoney)]
+    pub fn new_money(jacoby: bool, beaver: bool) -> Game {
+        let mut board = Board::new();
+        board.game = Match::money(jacoby, beaver);
+        Game::with_board(board)
+    }
+    #[wasm_bindgen(js_name = fromXgid)]
+    pub fn from_xgid(xgid: &str) -> Result<Game, JsValue> {
+        Board::from_xgid(xgid)
+            .map(Game::with_board)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    #[wasm_bindgen(js_name = fromXgId)]
+    pub fn from_xg_id(xgid: &str) -> Result<Game, JsValue> {
+        Board::from_xg_id(xgid)
+            .map(Game::with_board)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    // JSON array of actions, e.g. ["NoDouble", {"Roll": [3, 1]}, {"Move": [[8, 5, false]]}]
+    pub fn actions(&self) -> String {
+        serde_json::to_string(&self.board.actions()).unwrap()
+    }
+    pub fn act(&mut self, action: &str) -> Result<(), JsValue> {
+        self.try_act(action).map_err(|e| JsValue::from_str(&e))
+    }
+    pub fn state(&self) -> String {
+        format!("{:?}", self.board.state())
+    }
+    pub fn xgid(&self) -> String {
+        self.board.xgid()
+    }
+    #[wasm_bindgen(js_name = xgId)]
+    pub fn xg_id(&self) -> String {
+        self.board.xg_id()
+    }
+    pub fn display(&self) -> String {
+        self.board.to_string()
+    }
+    // JSON of the evaluated equities:
+    // {"actions": [[action, equity], ...], "equity": ..., "outcomes": {...} or null, "emg": ...}
+    pub fn analyze(&mut self) -> String {
+        let eq = self.evaluator.eval(&self.board);
+        let mut json = serde_json::to_value(&eq).unwrap();
+        json["emg"] = eq.emg(&self.board).into();
+        json.to_string()
+    }
+}
+
+impl Game {
+    fn with_board(board: Board) -> Game {
+        Game {
+            board,
+            evaluator: OpenEvaluator::new(),
+        }
+    }
+    fn try_act(&mut self, action: &str) -> Result<(), String> {
+        let act: Action = serde_json::from_str(action).map_err(|e| e.to_string())?;
+        if !self.board.actions().contains(&act) {
+            return Err(format!("illegal action: {}", action));
+        }
+        self.board.act(&act);
+        Ok(())
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn play_by_json() {
+        let mut g = Game::new();
+        assert_eq!(g.state(), "Init");
+        g.try_act(r#"{"InitRoll": [2, 1]}"#).unwrap();
+        assert_eq!(g.state(), "ToMove");
+        let actions: Vec<Action> = serde_json::from_str(&g.actions()).unwrap();
+        assert_eq!(actions.len(), 15);
+        let act = serde_json::to_string(&actions[0]).unwrap();
+        g.try_act(&act).unwrap();
+        assert_eq!(g.state(), "ToDouble");
+        assert!(g.try_act(r#""Take""#).is_err());
+        g.try_act(r#""NoDouble""#).unwrap();
+        assert_eq!(g.actions().matches("Roll").count(), 21);
+    }
+
+    #[test]
+    fn analyze() {
+        let mut g = Game::from_xgid("XGID=-A----------------------a-:0:0:1::0:0:0:1:10").unwrap();
+        let eq: serde_json::Value = serde_json::from_str(&g.analyze()).unwrap();
+        assert_eq!(eq["actions"].as_array().unwrap().len(), 2);
+        assert!((eq["equity"].as_f64().unwrap() - 1.).abs() < 1e-5);
+        assert!((eq["emg"].as_f64().unwrap() - 1.).abs() < 1e-5);
+        assert!((eq["outcomes"]["win"].as_f64().unwrap() - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn long_match() {
+        let mut g = Game::new_match(31);
+        let eq: serde_json::Value = serde_json::from_str(&g.analyze()).unwrap();
+        assert!((eq["equity"].as_f64().unwrap() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn met() {
+        assert!(try_load_met("0.5,x\npost,0.5").is_err());
+        assert!(try_load_met("0.5\n").is_err());
+        // The table in use again, since other tests share it. They may extend it at any
+        // time, which keeps the entries already there.
+        let text = met::current().write_csv();
+        let read = Met::parse(&text).unwrap();
+        try_load_met(&text).unwrap();
+        let loaded = met::current();
+        for a in 1..=read.length() {
+            for b in 1..=read.length() {
+                for crawford in [false, true] {
+                    assert_eq!(loaded.get(a, b, crawford), read.get(a, b, crawford));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_money() {
+        // a length of 0 is a money game, valued in points rather than from the MET
+        let mut g =
+            Game::from_xg_id("XGID=-A----------------------a-:0:0:1:00:0:0:3:0:10").unwrap();
+        let eq: serde_json::Value = serde_json::from_str(&g.analyze()).unwrap();
+        assert!((eq["equity"].as_f64().unwrap() - 1.).abs() < 1e-9);
+        assert_eq!(
+            g.xg_id(),
+            "XGID=-A----------------------a-:0:0:1:00:0:0:3:0:10"
+        );
+    }
+}